@@ -1,85 +1,55 @@
-use async_std::sync::{Arc, Mutex, RwLock};
-use futures::stream::SplitSink;
-use futures::{stream::SplitStream, SinkExt, StreamExt};
+use futures::{SinkExt, StreamExt};
 use http::Method;
-use log::{debug, error, info, warn};
+use log::{debug, error, info};
 use roa::logger::logger;
 use roa::preload::*;
 use roa::router::{allow, RouteTable, Router, RouterError};
 use roa::websocket::tungstenite::protocol::frame::{coding::CloseCode, CloseFrame};
-use roa::websocket::{tungstenite::Error as WsError, Message, SocketStream, Websocket};
+use roa::websocket::{
+    tungstenite::Error as WsError, Hub, HubSocket, Message, Websocket,
+};
 use roa::{App, Context};
-use slab::Slab;
 use std::borrow::Cow;
 use std::error::Error as StdError;
 
-type Sender = SplitSink<SocketStream, Message>;
-type Channel = Slab<Mutex<Sender>>;
-#[derive(Clone)]
-struct SyncChannel(Arc<RwLock<Channel>>);
+/// Capacity of the queue of each connection.
+const CAPACITY: usize = 128;
 
-impl SyncChannel {
-    fn new() -> Self {
-        Self(Arc::new(RwLock::new(Slab::new())))
-    }
-
-    async fn broadcast(&self, message: Message) {
-        let channel = self.0.read().await;
-        for (_, sender) in channel.iter() {
-            if let Err(err) = sender.lock().await.send(message.clone()).await {
-                error!("broadcast error: {}", err);
-            }
-        }
-    }
-
-    async fn send(&self, index: usize, message: Message) {
-        if let Err(err) = self.0.read().await[index].lock().await.send(message).await {
-            error!("message send error: {}", err)
-        }
-    }
-
-    async fn register(&self, sender: Sender) -> usize {
-        self.0.write().await.insert(Mutex::new(sender))
-    }
-
-    async fn deregister(&self, index: usize) -> Sender {
-        self.0.write().await.remove(index).into_inner()
-    }
+fn hub() -> Hub {
+    Hub::new().capacity(CAPACITY)
 }
 
 async fn handle_message(
-    ctx: &Context<SyncChannel>,
-    index: usize,
-    mut receiver: SplitStream<SocketStream>,
+    ctx: &Context<Hub>,
+    socket: &mut HubSocket,
 ) -> Result<(), WsError> {
-    while let Some(message) = receiver.next().await {
+    while let Some(message) = socket.next().await {
         let message = message?;
         match message {
             Message::Close(frame) => {
                 debug!("websocket connection close: {:?}", frame);
                 break;
             }
-            Message::Ping(data) => ctx.send(index, Message::Pong(data)).await,
-            Message::Pong(data) => warn!("ignored pong: {:?}", data),
-            msg => ctx.broadcast(msg).await,
+            // pings are replied automatically.
+            Message::Ping(_) | Message::Pong(_) => (),
+            msg => {
+                ctx.broadcast(msg);
+            }
         }
     }
     Ok(())
 }
 
-fn route(prefix: &'static str) -> Result<RouteTable<SyncChannel>, RouterError> {
+fn route(prefix: &'static str) -> Result<RouteTable<Hub>, RouterError> {
     Router::new()
         .on(
             "/chat",
             allow(
                 [Method::GET],
-                Websocket::new(|ctx: Context<SyncChannel>, stream| async move {
-                    let (sender, receiver) = stream.split();
-                    let index = ctx.register(sender).await;
-                    let result = handle_message(&ctx, index, receiver).await;
-                    let mut sender = ctx.deregister(index).await;
-                    if let Err(err) = result {
-                        let result = sender
+                Websocket::new(|ctx: Context<Hub>, stream| async move {
+                    let mut socket = ctx.connect(stream);
+                    if let Err(err) = handle_message(&ctx, &mut socket).await {
+                        let result = socket
                             .send(Message::Close(Some(CloseFrame {
                                 code: CloseCode::Invalid,
                                 reason: Cow::Owned(err.to_string()),
@@ -98,7 +68,7 @@ fn route(prefix: &'static str) -> Result<RouteTable<SyncChannel>, RouterError> {
 #[async_std::main]
 async fn main() -> Result<(), Box<dyn StdError>> {
     pretty_env_logger::init();
-    let app = App::state(SyncChannel::new()).gate(logger).end(route("/")?);
+    let app = App::state(hub()).gate(logger).end(route("/")?);
     app.listen("127.0.0.1:8000", |addr| {
         info!("Server is listening on {}", addr)
     })?
@@ -108,21 +78,21 @@ async fn main() -> Result<(), Box<dyn StdError>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{route, App, Message, SinkExt, StdError, StreamExt, SyncChannel};
+    use super::{hub, route, App, Message, SinkExt, StdError, StreamExt};
     use async_tungstenite::async_std::connect_async;
     use roa::preload::*;
     use std::time::Duration;
 
     #[async_std::test]
     async fn echo() -> Result<(), Box<dyn StdError>> {
-        let channel = SyncChannel::new();
-        let app = App::state(channel.clone()).end(route("/")?);
+        let hub = hub();
+        let app = App::state(hub.clone()).end(route("/")?);
         let (addr, server) = app.run()?;
         async_std::task::spawn(server);
         let (ws_stream, _) = connect_async(format!("ws://{}/chat", addr)).await?;
         let (mut sender, mut recv) = ws_stream.split();
         async_std::task::sleep(Duration::from_secs(1)).await;
-        assert_eq!(1, channel.0.read().await.len());
+        assert_eq!(1, hub.len());
 
         // ping
         sender
@@ -135,14 +105,14 @@ mod tests {
         // close
         sender.send(Message::Close(None)).await?;
         async_std::task::sleep(Duration::from_secs(1)).await;
-        assert_eq!(0, channel.0.read().await.len());
+        assert_eq!(0, hub.len());
         Ok(())
     }
 
     #[async_std::test]
     async fn broadcast() -> Result<(), Box<dyn StdError>> {
-        let channel = SyncChannel::new();
-        let app = App::state(channel.clone()).end(route("/")?);
+        let hub = hub();
+        let app = App::state(hub.clone()).end(route("/")?);
         let (addr, server) = app.run()?;
         async_std::task::spawn(server);
         let url = format!("ws://{}/chat", addr);
@@ -160,7 +130,7 @@ mod tests {
             });
         }
         async_std::task::sleep(Duration::from_secs(1)).await;
-        assert_eq!(100, channel.0.read().await.len());
+        assert_eq!(100, hub.len());
 
         let (ws_stream, _) = connect_async(url).await?;
         let (mut sender, mut recv) = ws_stream.split();
@@ -169,7 +139,7 @@ mod tests {
             .await
             .is_ok());
         async_std::task::sleep(Duration::from_secs(2)).await;
-        assert_eq!(1, channel.0.read().await.len());
+        assert_eq!(1, hub.len());
 
         let mut counter = 0i32;
         while let Some(item) = recv.next().await {
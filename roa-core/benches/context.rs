@@ -0,0 +1,52 @@
+use async_std::task::block_on;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures::io::Cursor;
+use hyper::service::Service;
+use hyper::Body;
+use roa_core::http::Request;
+use roa_core::{async_trait, AddrStream, App, Context, Middleware, Next, Result};
+
+const CLONES: usize = 16;
+
+struct Fill(usize);
+
+#[async_trait(?Send)]
+impl<'a> Middleware<'a> for Fill {
+    async fn handle(&'a self, ctx: &'a mut Context, next: Next<'a>) -> Result {
+        for i in 0..self.0 {
+            ctx.store(i.to_string(), i);
+        }
+        next.await
+    }
+}
+
+async fn clone_context(ctx: &mut Context) -> Result {
+    for _ in 0..CLONES {
+        let _ = ctx.clone();
+    }
+    Ok(())
+}
+
+fn bench_clone(c: &mut Criterion) {
+    let mut group = c.benchmark_group("context clone");
+    for variables in [0usize, 16, 256].iter() {
+        let variables = *variables;
+        let mut app = App::new().gate(Fill(variables)).end(clone_context);
+        let stream = AddrStream::new(([127, 0, 0, 1], 0).into(), Cursor::new(Vec::new()));
+        let service = block_on(app.call(&stream)).unwrap();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(variables),
+            &variables,
+            |b, _| {
+                b.iter(|| {
+                    let mut service = service.clone();
+                    block_on(service.call(Request::new(Body::empty()))).unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_clone);
+criterion_main!(benches);
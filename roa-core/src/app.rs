@@ -3,12 +3,16 @@ mod runtime;
 
 mod future;
 mod stream;
+use crate::response::HttpResponseBody;
 use crate::{
-    Chain, Context, Endpoint, Middleware, MiddlewareExt, Request, Response, State,
+    Body, Chain, Context, Endpoint, Middleware, MiddlewareExt, Request, Response, State,
 };
 use future::SendFuture;
 use futures::io::{AsyncRead, AsyncWrite};
-use http::{Request as HttpRequest, Response as HttpResponse};
+use http::header::{HeaderValue, CONTENT_LENGTH};
+use http::{
+    Method, Request as HttpRequest, Response as HttpResponse, StatusCode, Version,
+};
 use hyper::service::Service;
 use hyper::Body as HyperBody;
 use hyper::Server;
@@ -211,7 +215,7 @@ where
 type HttpFuture = Pin<
     Box<
         dyn 'static
-            + Future<Output = Result<HttpResponse<HyperBody>, Infallible>>
+            + Future<Output = Result<HttpResponse<HttpResponseBody>, Infallible>>
             + Send,
     >,
 >;
@@ -221,7 +225,7 @@ where
     S: State,
     E: for<'a> Endpoint<'a, S>,
 {
-    type Response = HttpResponse<HyperBody>;
+    type Response = HttpResponse<HttpResponseBody>;
     type Error = Infallible;
     type Future = HttpFuture;
     impl_poll_ready!();
@@ -231,7 +235,7 @@ where
         let service = self.clone();
         Box::pin(async move {
             let serve_future = SendFuture(Box::pin(service.serve(req.into())));
-            Ok(serve_future.await.into())
+            Ok(serve_future.await.into_http())
         })
     }
 }
@@ -275,10 +279,42 @@ impl<S, E> HttpService<S, E> {
                     .await;
             }
         }
+        set_content_length(&mut ctx.resp);
+        if ctx.method() == Method::HEAD {
+            ctx.resp.body = Body::empty();
+            ctx.resp.clear_trailers();
+        }
+        if ctx.resp.has_trailers() && ctx.version() != Version::HTTP_2 {
+            // hyper cannot write chunked trailers over HTTP/1.x.
+            let version = ctx.version();
+            ctx.resp.clear_trailers();
+            ctx.exec
+                .spawn_blocking(move || {
+                    log::warn!("trailers are dropped over {:?} connections", version)
+                })
+                .await;
+        }
         ctx.resp
     }
 }
 
+/// Set "Content-Length" if it's not set and the length of body is known,
+/// so that hyper will not use chunked encoding for sized streams.
+#[inline]
+fn set_content_length(resp: &mut Response) {
+    let status = resp.status;
+    let no_body = status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED;
+    if no_body || resp.headers.contains_key(CONTENT_LENGTH) {
+        return;
+    }
+    if let Some(length) = resp.content_length() {
+        resp.headers
+            .insert(CONTENT_LENGTH, HeaderValue::from(length));
+    }
+}
+
 impl<S: Clone, E> Clone for HttpService<S, E> {
     fn clone(&self) -> Self {
         Self {
@@ -292,8 +328,9 @@ impl<S: Clone, E> Clone for HttpService<S, E> {
 
 #[cfg(all(test, feature = "runtime"))]
 mod tests {
-    use crate::{App, Request};
-    use http::StatusCode;
+    use crate::{App, Context, Request};
+    use http::header::CONTENT_LENGTH;
+    use http::{Method, StatusCode};
 
     #[async_std::test]
     async fn gate_simple() -> Result<(), Box<dyn std::error::Error>> {
@@ -302,4 +339,25 @@ mod tests {
         assert_eq!(StatusCode::OK, resp.status);
         Ok(())
     }
+
+    #[async_std::test]
+    async fn content_length_and_head() -> Result<(), Box<dyn std::error::Error>> {
+        async fn end(ctx: &mut Context) -> crate::Result {
+            ctx.resp
+                .write("Hello, ")
+                .write_sized_reader(&b"World"[..], 5);
+            Ok(())
+        }
+        let service = App::new().end(end).http_service();
+        let resp = service.clone().serve(Request::default()).await;
+        assert_eq!("12", resp.headers[CONTENT_LENGTH]);
+        assert_eq!(Some(12), resp.content_length());
+
+        let mut req = Request::default();
+        req.method = Method::HEAD;
+        let resp = service.serve(req).await;
+        assert_eq!("12", resp.headers[CONTENT_LENGTH]);
+        assert_eq!(Some(0), resp.content_length());
+        Ok(())
+    }
 }
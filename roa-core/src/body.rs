@@ -1,7 +1,10 @@
 use bytes::{Buf, Bytes, BytesMut};
+use futures::channel::mpsc::{channel, SendError, Sender};
 use futures::future::ok;
 use futures::io::{self, AsyncRead};
-use futures::stream::{once, Stream, StreamExt};
+use futures::sink::SinkExt;
+use futures::stream::{iter, once, Stream, StreamExt};
+use std::collections::VecDeque;
 use std::mem;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -22,6 +25,7 @@ const DEFAULT_CHUNK_SIZE: usize = 4096;
 ///     Ok(match body {
 ///         Body::Empty => Bytes::new(),
 ///         Body::Once(bytes) => bytes,
+///         Body::Chunks(chunks) => chunks.into_iter().flatten().collect(),
 ///         Body::Stream(mut stream) => {
 ///             let mut bytes = Vec::new();
 ///             while let Some(item) = stream.next().await {
@@ -39,15 +43,28 @@ pub enum Body {
     /// Bytes kind.
     Once(Bytes),
 
+    /// Vectored bytes kind, consecutive in-memory writes are batched in it.
+    Chunks(VecDeque<Bytes>),
+
     /// Stream kind.
     Stream(Segment),
 }
 
-/// A boxed stream.
-#[derive(Default)]
-pub struct Segment(
-    Option<Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Sync + Send + 'static>>>,
-);
+/// A boxed stream with an optional exact size,
+/// bytes written after the stream are batched in a tail.
+pub struct Segment {
+    stream:
+        Option<Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Sync + Send + 'static>>>,
+    tail: VecDeque<Bytes>,
+    size: Option<u64>,
+}
+
+/// A sender to push data into a body, created by `Body::channel`.
+///
+/// It can be cloned to push data from multiple tasks,
+/// the body ends when all senders are dropped.
+#[derive(Clone)]
+pub struct BodySender(Sender<io::Result<Bytes>>);
 
 impl Body {
     /// Construct an empty body.
@@ -68,7 +85,22 @@ impl Body {
     where
         S: Stream<Item = io::Result<Bytes>> + Sync + Send + 'static,
     {
-        Body::Stream(Segment::new(stream))
+        Body::Stream(Segment::new(stream, None))
+    }
+
+    /// Get the exact length of body, return `None` if it's unknown.
+    ///
+    /// The length is known if all parts are bytes, sized streams or sized readers.
+    #[inline]
+    pub fn content_length(&self) -> Option<u64> {
+        match self {
+            Body::Empty => Some(0),
+            Body::Once(bytes) => Some(bytes.len() as u64),
+            Body::Chunks(chunks) => {
+                Some(chunks.iter().map(|chunk| chunk.len() as u64).sum())
+            }
+            Body::Stream(segment) => segment.size,
+        }
     }
 
     /// Write stream.
@@ -77,16 +109,46 @@ impl Body {
         &mut self,
         stream: impl Stream<Item = io::Result<Bytes>> + Sync + Send + 'static,
     ) -> &mut Self {
+        self.chain(stream, None)
+    }
+
+    /// Write stream with exact size.
+    ///
+    /// The size must be exact, otherwise the response will be broken.
+    #[inline]
+    pub fn write_sized_stream(
+        &mut self,
+        stream: impl Stream<Item = io::Result<Bytes>> + Sync + Send + 'static,
+        size: u64,
+    ) -> &mut Self {
+        self.chain(stream, Some(size))
+    }
+
+    /// Chain a stream with an optional exact size.
+    #[inline]
+    fn chain(
+        &mut self,
+        stream: impl Stream<Item = io::Result<Bytes>> + Sync + Send + 'static,
+        size: Option<u64>,
+    ) -> &mut Self {
+        let total = self
+            .content_length()
+            .and_then(|length| size.map(|size| length + size));
         match self {
             Body::Empty => {
-                *self = Self::stream(stream);
+                *self = Body::Stream(Segment::new(stream, total));
             }
             Body::Once(bytes) => {
                 let stream = once(ok(mem::take(bytes))).chain(stream);
-                *self = Self::stream(stream);
+                *self = Body::Stream(Segment::new(stream, total));
+            }
+            Body::Chunks(chunks) => {
+                let stream = iter(mem::take(chunks).into_iter().map(Ok)).chain(stream);
+                *self = Body::Stream(Segment::new(stream, total));
             }
             Body::Stream(segment) => {
-                *self = Self::stream(mem::take(segment).chain(stream));
+                let stream = mem::take(segment).chain(stream);
+                *self = Body::Stream(Segment::new(stream, total));
             }
         }
         self
@@ -111,16 +173,68 @@ impl Body {
         self.write_stream(ReaderStream::new(reader, chunk_size))
     }
 
+    /// Write reader with exact size and default chunk size,
+    /// like a file with length in metadata.
+    ///
+    /// The size must be exact, otherwise the response will be broken.
+    #[inline]
+    pub fn write_sized_reader(
+        &mut self,
+        reader: impl AsyncRead + Sync + Send + Unpin + 'static,
+        size: u64,
+    ) -> &mut Self {
+        self.write_sized_stream(ReaderStream::new(reader, DEFAULT_CHUNK_SIZE), size)
+    }
+
+    /// Write a channel with capacity, return a sender to push data into it.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::{App, Context, Result};
+    ///
+    /// async fn end(ctx: &mut Context) -> Result {
+    ///     let mut sender = ctx.resp.channel(16);
+    ///     ctx.exec.spawn(async move {
+    ///         for i in 0..10 {
+    ///             // wait if the channel is full
+    ///             if sender.send(format!("line {}\n", i)).await.is_err() {
+    ///                 // the connection is closed
+    ///                 return;
+    ///             }
+    ///         }
+    ///         // the body ends when sender is dropped
+    ///     });
+    ///     Ok(())
+    /// }
+    ///
+    /// let app = App::new().end(end);
+    /// ```
+    #[inline]
+    pub fn channel(&mut self, capacity: usize) -> BodySender {
+        let (sender, receiver) = channel(capacity);
+        self.write_stream(receiver);
+        BodySender(sender)
+    }
+
     /// Write `Bytes`.
+    ///
+    /// Consecutive writes are batched without boxing a new stream.
     #[inline]
     pub fn write(&mut self, data: impl Into<Bytes>) -> &mut Self {
+        let data = data.into();
         match self {
             Body::Empty => {
-                *self = Self::once(data.into());
-                self
+                *self = Self::once(data);
             }
-            body => body.write_stream(once(ok(data.into()))),
+            Body::Once(bytes) => {
+                let chunks = vec![mem::take(bytes), data];
+                *self = Body::Chunks(chunks.into());
+            }
+            Body::Chunks(chunks) => chunks.push_back(data),
+            Body::Stream(segment) => segment.push(data),
         }
+        self
     }
 }
 
@@ -128,8 +242,32 @@ impl Segment {
     #[inline]
     fn new(
         stream: impl Stream<Item = io::Result<Bytes>> + Sync + Send + 'static,
+        size: Option<u64>,
     ) -> Self {
-        Self(Some(Box::pin(stream)))
+        Self {
+            stream: Some(Box::pin(stream)),
+            tail: VecDeque::new(),
+            size,
+        }
+    }
+
+    /// Push bytes to the tail.
+    #[inline]
+    fn push(&mut self, data: Bytes) {
+        self.size = self.size.map(|size| size + data.len() as u64);
+        self.tail.push_back(data);
+    }
+}
+
+/// An empty segment.
+impl Default for Segment {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            stream: None,
+            tail: VecDeque::new(),
+            size: Some(0),
+        }
     }
 }
 
@@ -139,6 +277,9 @@ impl From<Body> for hyper::Body {
         match body {
             Body::Empty => hyper::Body::empty(),
             Body::Once(bytes) => hyper::Body::from(bytes),
+            Body::Chunks(chunks) => hyper::Body::wrap_stream(iter(
+                chunks.into_iter().map(Ok::<_, io::Error>),
+            )),
             Body::Stream(stream) => hyper::Body::wrap_stream(stream),
         }
     }
@@ -151,6 +292,29 @@ impl Default for Body {
     }
 }
 
+impl BodySender {
+    /// Send data, wait if the channel is full.
+    ///
+    /// Return an error if the body has been dropped, e.g. the connection is closed.
+    #[inline]
+    pub async fn send(&mut self, data: impl Into<Bytes>) -> io::Result<()> {
+        self.0.send(Ok(data.into())).await.map_err(closed)
+    }
+
+    /// Abort the response with an error, the connection will be closed.
+    #[inline]
+    pub async fn abort(mut self, err: io::Error) {
+        // the body has been dropped if it fails.
+        let _ = self.0.send(Err(err)).await;
+    }
+}
+
+/// Convert error of a closed channel.
+#[inline]
+fn closed(err: SendError) -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, err)
+}
+
 pub struct ReaderStream<R> {
     chunk_size: usize,
     reader: R,
@@ -200,6 +364,7 @@ impl Stream for Body {
                 *self = Body::empty();
                 Poll::Ready(Some(Ok(data)))
             }
+            Body::Chunks(chunks) => Poll::Ready(chunks.pop_front().map(Ok)),
             Body::Stream(stream) => Pin::new(stream).poll_next(cx),
         }
     }
@@ -212,10 +377,13 @@ impl Stream for Segment {
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        match self.0 {
-            None => Poll::Ready(None),
-            Some(ref mut stream) => stream.as_mut().poll_next(cx),
+        if let Some(ref mut stream) = self.stream {
+            match futures::ready!(stream.as_mut().poll_next(cx)) {
+                None => self.stream = None,
+                item => return Poll::Ready(item),
+            }
         }
+        Poll::Ready(self.tail.pop_front().map(Ok))
     }
 }
 
@@ -223,6 +391,7 @@ impl Stream for Segment {
 mod tests {
     use super::Body;
     use async_std::fs::File;
+    use async_std::task::spawn;
     use futures::{AsyncReadExt, TryStreamExt};
     use std::io;
 
@@ -255,6 +424,25 @@ mod tests {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn body_chunks() -> std::io::Result<()> {
+        let mut body = Body::default();
+        body.write("He").write("llo, ").write("World");
+        match body {
+            Body::Chunks(ref chunks) => assert_eq!(3, chunks.len()),
+            _ => panic!("consecutive writes should be batched"),
+        }
+        body.write_reader(File::open("../assets/author.txt").await?)
+            .write(", ")
+            .write("World");
+        match body {
+            Body::Stream(ref segment) => assert_eq!(2, segment.tail.len()),
+            _ => panic!("body should be a stream"),
+        }
+        assert_eq!("Hello, WorldHexilee, World", read_body(body).await?);
+        Ok(())
+    }
+
     #[async_std::test]
     async fn body_composed() -> std::io::Result<()> {
         let mut body = Body::empty();
@@ -266,4 +454,65 @@ mod tests {
         assert_eq!("Hello, HexileeHexilee.", read_body(body).await?);
         Ok(())
     }
+
+    #[async_std::test]
+    async fn body_channel() -> std::io::Result<()> {
+        let mut body = Body::empty();
+        body.write("Hello, ");
+        let mut sender = body.channel(1);
+        let task = spawn(async move {
+            for data in &["Hexilee", "Hexilee", "."] {
+                sender.send(*data).await?;
+            }
+            Ok::<_, std::io::Error>(())
+        });
+        body.write("!");
+        assert_eq!("Hello, HexileeHexilee.!", read_body(body).await?);
+        task.await
+    }
+
+    #[async_std::test]
+    async fn body_channel_abort() -> std::io::Result<()> {
+        let mut body = Body::empty();
+        let mut sender = body.channel(1);
+        spawn(async move {
+            sender.send("Hello").await?;
+            sender
+                .abort(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionAborted,
+                    "abort",
+                ))
+                .await;
+            Ok::<_, std::io::Error>(())
+        });
+        let err = read_body(body).await.unwrap_err();
+        assert_eq!("abort", err.to_string());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn body_channel_closed() {
+        let mut body = Body::empty();
+        let mut sender = body.channel(1);
+        drop(body);
+        assert!(sender.send("Hello").await.is_err());
+    }
+
+    #[async_std::test]
+    async fn body_content_length() -> std::io::Result<()> {
+        let mut body = Body::empty();
+        assert_eq!(Some(0), body.content_length());
+        body.write("He").write("llo, ");
+        assert_eq!(Some(7), body.content_length());
+        body.write_sized_reader(File::open("../assets/author.txt").await?, 7);
+        assert_eq!(Some(14), body.content_length());
+        body.write(".");
+        assert_eq!(Some(15), body.content_length());
+        body.write_reader(File::open("../assets/author.txt").await?);
+        assert_eq!(None, body.content_length());
+        body.write(".");
+        assert_eq!(None, body.content_length());
+        assert_eq!("Hello, Hexilee.Hexilee.", read_body(body).await?);
+        Ok(())
+    }
 }
@@ -338,6 +338,10 @@ impl<S> DerefMut for Context<S> {
     }
 }
 
+/// Clone a context without request and response.
+///
+/// The storage is shared until either side writes to it,
+/// so cloning is cheap as long as the state is cheap to clone.
 impl<S: Clone> Clone for Context<S> {
     #[inline]
     fn clone(&self) -> Self {
@@ -394,6 +398,23 @@ mod tests_with_runtime {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn clone_isolation() -> Result<(), Box<dyn Error>> {
+        async fn test(ctx: &mut Context) -> Result<(), Status> {
+            ctx.store("id", "1");
+            let mut cloned = ctx.clone();
+            assert_eq!("1", *cloned.load::<&'static str>("id").unwrap());
+            cloned.store("id", "2");
+            assert_eq!("1", *ctx.load::<&'static str>("id").unwrap());
+            assert_eq!("2", *cloned.load::<&'static str>("id").unwrap());
+            Ok(())
+        }
+        let service = App::new().end(test).http_service();
+        let resp = service.serve(Request::default()).await;
+        assert_eq!(StatusCode::OK, resp.status);
+        Ok(())
+    }
+
     #[async_std::test]
     async fn must_get() -> Result<(), Box<dyn Error>> {
         use http::header::{CONTENT_TYPE, HOST};
@@ -12,11 +12,15 @@ pub trait Value: Any + Send + Sync {}
 
 impl<V> Value for V where V: Any + Send + Sync {}
 
+/// A bucket of key-value pairs in the same scope.
+type Bucket = HashMap<Cow<'static, str>, Arc<dyn Any + Send + Sync>>;
+
 /// A context scoped storage.
+///
+/// Both the scope map and the buckets are reference-counted and copied on write,
+/// so cloning a storage is O(1) while mutations stay isolated.
 #[derive(Clone)]
-pub struct Storage(
-    HashMap<TypeId, HashMap<Cow<'static, str>, Arc<dyn Any + Send + Sync>>>,
-);
+pub struct Storage(Arc<HashMap<TypeId, Arc<Bucket>>>);
 
 /// A wrapper of Arc.
 ///
@@ -98,7 +102,7 @@ impl Storage {
     /// Construct an empty Bucket.
     #[inline]
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self(Arc::new(HashMap::new()))
     }
 
     /// Inserts a key-value pair into the storage.
@@ -107,22 +111,17 @@ impl Storage {
     ///
     /// If the storage did have this key present, the value is updated, and the old
     /// value is returned.
-    pub fn insert<S, K, V>(&mut self, scope: S, key: K, value: V) -> Option<Arc<V>>
+    pub fn insert<S, K, V>(&mut self, _scope: S, key: K, value: V) -> Option<Arc<V>>
     where
         S: Any,
         K: Into<Cow<'static, str>>,
         V: Value,
     {
         let id = TypeId::of::<S>();
-        match self.0.get_mut(&id) {
-            Some(bucket) => bucket
-                .insert(key.into(), Arc::new(value))
-                .and_then(|value| Some(value.downcast().ok()?)),
-            None => {
-                self.0.insert(id, HashMap::new());
-                self.insert(scope, key, value)
-            }
-        }
+        let bucket = Arc::make_mut(&mut self.0).entry(id).or_default();
+        Arc::make_mut(bucket)
+            .insert(key.into(), Arc::new(value))
+            .and_then(|value| Some(value.downcast().ok()?))
     }
 
     /// If the storage did not have this key present, [`None`] is returned.
@@ -175,6 +174,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn copy_on_write() {
+        struct Scope;
+
+        let mut storage = Storage::default();
+        storage.insert(Scope, "id", "1");
+        let mut cloned = storage.clone();
+        assert!(Arc::ptr_eq(&storage.0, &cloned.0));
+
+        cloned.insert(Scope, "id", "2");
+        cloned.insert(Scope, "name", "Hexilee");
+        assert!(!Arc::ptr_eq(&storage.0, &cloned.0));
+        assert_eq!("1", *storage.get::<Scope, &'static str>("id").unwrap());
+        assert!(storage.get::<Scope, &'static str>("name").is_none());
+        assert_eq!("2", *cloned.get::<Scope, &'static str>("id").unwrap());
+        assert_eq!("Hexilee", *cloned.get::<Scope, &'static str>("name").unwrap());
+    }
+
     #[test]
     fn variable() {
         assert_eq!(
@@ -38,10 +38,10 @@ pub use state::State;
 pub use request::Request;
 
 #[doc(inline)]
-pub use response::Response;
+pub use response::{Response, Trailers};
 
 #[doc(inline)]
-pub use body::Body;
+pub use body::{Body, BodySender};
 
 pub use http;
 
@@ -1,6 +1,14 @@
 //! A module for Response and its body
+use bytes::Bytes;
+use futures::Stream;
+use http::header::IntoHeaderName;
 use http::{HeaderMap, HeaderValue, StatusCode, Version};
+use hyper::body::HttpBody;
+use std::io;
 use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
 
 pub use crate::Body;
 
@@ -17,6 +25,26 @@ pub struct Response {
 
     /// Response body.
     pub body: Body,
+
+    trailers: Option<Trailers>,
+}
+
+/// Trailers of a response, sent after the body finishes.
+///
+/// It can be cloned and moved into a body stream,
+/// so that trailers like checksum can be computed while streaming.
+///
+/// Trailers are only sent over HTTP/2, as chunked trailers are not written by
+/// the HTTP/1 server of hyper. Over HTTP/1.x connections they are dropped
+/// with a warning logged, so gRPC-web over HTTP/1 is not covered,
+/// its status must be encoded into the body as a trailer frame by the application.
+#[derive(Debug, Clone, Default)]
+pub struct Trailers(Arc<Mutex<HeaderMap<HeaderValue>>>);
+
+/// Body of http response, sending trailers after body.
+pub struct HttpResponseBody {
+    body: Body,
+    trailers: Option<Trailers>,
 }
 
 impl Response {
@@ -27,23 +55,135 @@ impl Response {
             version: Version::default(),
             headers: HeaderMap::default(),
             body: Body::default(),
+            trailers: None,
         }
     }
 
+    /// Get trailers of this response, they are sent after the body finishes over HTTP/2.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::{App, Context, Result};
+    ///
+    /// async fn end(ctx: &mut Context) -> Result {
+    ///     let trailers = ctx.resp.trailers();
+    ///     ctx.resp.write("Hello, World");
+    ///     trailers.insert("grpc-status", "0".parse()?);
+    ///     Ok(())
+    /// }
+    ///
+    /// let app = App::new().end(end);
+    /// ```
     #[inline]
-    fn into_resp(self) -> http::Response<hyper::Body> {
+    pub fn trailers(&mut self) -> Trailers {
+        self.trailers.get_or_insert_with(Trailers::default).clone()
+    }
+
+    /// Whether trailers are requested by `Response::trailers`.
+    #[inline]
+    pub(crate) fn has_trailers(&self) -> bool {
+        self.trailers.is_some()
+    }
+
+    /// Drop trailers, like body of HEAD requests.
+    #[inline]
+    pub(crate) fn clear_trailers(&mut self) {
+        self.trailers = None;
+    }
+
+    #[inline]
+    fn into_parts(self) -> (http::response::Parts, HttpResponseBody) {
         let (mut parts, _) = http::Response::new(()).into_parts();
         let Response {
             status,
             version,
             headers,
             body,
+            trailers,
         } = self;
         parts.status = status;
         parts.version = version;
         parts.headers = headers;
+        (parts, HttpResponseBody { body, trailers })
+    }
+
+    #[inline]
+    fn into_resp(self) -> http::Response<hyper::Body> {
+        let (parts, HttpResponseBody { body, .. }) = self.into_parts();
         http::Response::from_parts(parts, body.into())
     }
+
+    /// Convert into http response with trailers.
+    #[inline]
+    pub(crate) fn into_http(self) -> http::Response<HttpResponseBody> {
+        let (parts, body) = self.into_parts();
+        http::Response::from_parts(parts, body)
+    }
+}
+
+impl Trailers {
+    /// Insert a trailer, return the old value if it's present.
+    #[inline]
+    pub fn insert(
+        &self,
+        name: impl IntoHeaderName,
+        value: HeaderValue,
+    ) -> Option<HeaderValue> {
+        self.lock().insert(name, value)
+    }
+
+    /// Append a trailer, return false if it's not present before.
+    #[inline]
+    pub fn append(&self, name: impl IntoHeaderName, value: HeaderValue) -> bool {
+        self.lock().append(name, value)
+    }
+
+    /// Take all trailers.
+    #[inline]
+    fn take(&self) -> HeaderMap<HeaderValue> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, HeaderMap<HeaderValue>> {
+        // a panic while inserting a header cannot break the map.
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl HttpBody for HttpResponseBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    #[inline]
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<Bytes>>> {
+        Pin::new(&mut self.body).poll_next(cx)
+    }
+
+    #[inline]
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<HeaderMap>>> {
+        let trailers = self
+            .trailers
+            .as_ref()
+            .map(Trailers::take)
+            .filter(|trailers| !trailers.is_empty());
+        Poll::Ready(Ok(trailers))
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        match self.body {
+            Body::Empty => self.trailers.is_none(),
+            _ => false,
+        }
+    }
 }
 
 impl Deref for Response {
@@ -74,3 +214,42 @@ impl Default for Response {
         Self::new()
     }
 }
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use crate::{App, Context};
+    use hyper::body::HttpBody;
+    use hyper::service::Service;
+    use hyper::Body;
+
+    #[async_std::test]
+    async fn send_trailers() -> Result<(), Box<dyn std::error::Error>> {
+        async fn end(ctx: &mut Context) -> crate::Result {
+            let trailers = ctx.resp.trailers();
+            let mut sender = ctx.resp.channel(1);
+            ctx.exec.spawn(async move {
+                let mut length = 0;
+                for data in &["Hello", ", ", "World"] {
+                    length += data.len();
+                    sender.send(*data).await?;
+                }
+                trailers.insert("x-length", length.into());
+                Ok::<_, std::io::Error>(())
+            });
+            Ok(())
+        }
+        let mut service = App::new().end(end).http_service();
+        let mut req = http::Request::new(Body::empty());
+        *req.version_mut() = http::Version::HTTP_2;
+        let resp = service.call(req).await?;
+        let mut body = resp.into_body();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk?);
+        }
+        assert_eq!(b"Hello, World", &*data);
+        let trailers = body.trailers().await?.expect("trailers should be sent");
+        assert_eq!("12", trailers["x-length"]);
+        Ok(())
+    }
+}
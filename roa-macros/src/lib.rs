@@ -0,0 +1,42 @@
+#![cfg_attr(feature = "docs", feature(external_doc))]
+#![cfg_attr(feature = "docs", doc(include = "../README.md"))]
+#![cfg_attr(feature = "docs", warn(missing_docs))]
+
+extern crate proc_macro;
+
+mod route;
+
+use proc_macro::TokenStream;
+
+macro_rules! impl_method_attributes {
+    ($($name:ident => $method:ident, $doc:expr;)*) => {
+        $(
+            #[doc = $doc]
+            #[proc_macro_attribute]
+            pub fn $name(args: TokenStream, input: TokenStream) -> TokenStream {
+                route::expand_route(stringify!($method), args, input)
+            }
+        )*
+    };
+}
+
+impl_method_attributes! {
+    get => GET, "Declare a route on GET, like `#[get(\"/user/:id\")]`.";
+    post => POST, "Declare a route on POST, like `#[post(\"/user\")]`.";
+    put => PUT, "Declare a route on PUT, like `#[put(\"/user/:id\")]`.";
+    patch => PATCH, "Declare a route on PATCH, like `#[patch(\"/user/:id\")]`.";
+    options => OPTIONS, "Declare a route on OPTIONS, like `#[options(\"/user\")]`.";
+    delete => DELETE, "Declare a route on DELETE, like `#[delete(\"/user/:id\")]`.";
+    head => HEAD, "Declare a route on HEAD, like `#[head(\"/user/:id\")]`.";
+    trace => TRACE, "Declare a route on TRACE, like `#[trace(\"/user\")]`.";
+    connect => CONNECT, "Declare a route on CONNECT, like `#[connect(\"/user\")]`.";
+}
+
+/// Build a `roa::router::Router` from handlers declared by route attributes,
+/// like `routes![index, get_user, create_user]`.
+///
+/// Handlers on the same path are grouped into one `roa::router::Dispatcher`.
+#[proc_macro]
+pub fn routes(input: TokenStream) -> TokenStream {
+    route::expand_routes(input)
+}
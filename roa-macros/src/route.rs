@@ -0,0 +1,172 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use roa_path::validate;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Error, FnArg, GenericArgument, ItemFn, LitStr, Path,
+    PathArguments, Token, Type,
+};
+
+/// Expand a route attribute.
+pub fn expand_route(method: &str, args: TokenStream, input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(args as LitStr);
+    let item = parse_macro_input!(input as ItemFn);
+    match route(Ident::new(method, Span::call_site()), path, item) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Expand `routes!`.
+pub fn expand_routes(input: TokenStream) -> TokenStream {
+    let parser = Punctuated::<Path, Token![,]>::parse_terminated;
+    let routes = parse_macro_input!(input with parser).into_iter();
+    let tokens = quote! {
+        {
+            let mut dispatchers: ::std::vec::Vec<(&'static str, ::roa::router::Dispatcher<_>)> =
+                ::std::vec::Vec::new();
+            #(
+                match dispatchers.iter().position(|(path, _)| *path == #routes::PATH) {
+                    ::std::option::Option::Some(index) => {
+                        let (path, dispatcher) = dispatchers.remove(index);
+                        dispatchers.insert(
+                            index,
+                            (path, dispatcher.method(#routes::METHOD, #routes {})),
+                        );
+                    }
+                    ::std::option::Option::None => dispatchers.push((
+                        #routes::PATH,
+                        ::roa::router::Dispatcher::default().method(#routes::METHOD, #routes {}),
+                    )),
+                }
+            )*
+            dispatchers
+                .into_iter()
+                .fold(::roa::router::Router::new(), |router, (path, dispatcher)| {
+                    router.on(path, dispatcher)
+                })
+        }
+    };
+    tokens.into()
+}
+
+fn route(method: Ident, path: LitStr, item: ItemFn) -> syn::Result<TokenStream2> {
+    if let Err(err) = validate(&path.value()) {
+        return Err(Error::new(path.span(), err));
+    }
+    let sig = &item.sig;
+    if sig.asyncness.is_none() {
+        return Err(Error::new(
+            sig.fn_token.span(),
+            "route handler must be async",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "route handler cannot be generic",
+        ));
+    }
+
+    let name = &sig.ident;
+    let vis = &item.vis;
+    let mut types = Vec::new();
+    for input in sig.inputs.iter() {
+        match input {
+            FnArg::Typed(arg) => types.push(&*arg.ty),
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(
+                    receiver.span(),
+                    "route handler cannot take self",
+                ))
+            }
+        }
+    }
+
+    let endpoint = match context_state(&types)? {
+        // async fn(&mut Context<S>) -> impl IntoResponse
+        Some(state) => quote! {
+            #[::roa::async_trait(?Send)]
+            impl<'a> ::roa::Endpoint<'a, #state> for #name {
+                #[inline]
+                async fn call(&'a self, ctx: &'a mut ::roa::Context<#state>) -> ::roa::Result {
+                    let value = #name(&mut *ctx).await;
+                    ::roa::response::IntoResponse::into_response(value, &mut ctx.resp)
+                }
+            }
+        },
+        // async fn(A, B, ...) -> impl IntoResponse, where A, B: FromContext<S>
+        None => {
+            let args: Vec<Ident> = (0..types.len())
+                .map(|index| format_ident!("arg{}", index))
+                .collect();
+            quote! {
+                #[::roa::async_trait(?Send)]
+                impl<'a, S> ::roa::Endpoint<'a, S> for #name
+                where
+                    S: 'a,
+                    (#(#types,)*): ::roa::extract::FromContext<S>,
+                {
+                    #[inline]
+                    async fn call(&'a self, ctx: &'a mut ::roa::Context<S>) -> ::roa::Result {
+                        let (#(#args,)*) =
+                            <(#(#types,)*) as ::roa::extract::FromContext<S>>::from_context(ctx)
+                                .await?;
+                        let value = #name(#(#args),*).await;
+                        ::roa::response::IntoResponse::into_response(value, &mut ctx.resp)
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #item
+
+        #[allow(non_camel_case_types)]
+        #[doc(hidden)]
+        #vis struct #name {}
+
+        impl #name {
+            #vis const PATH: &'static str = #path;
+            #vis const METHOD: ::roa::http::Method = ::roa::http::Method::#method;
+        }
+
+        #endpoint
+    })
+}
+
+/// Get state type if the handler takes only a `&mut Context<S>`.
+fn context_state(types: &[&Type]) -> syn::Result<Option<Type>> {
+    if types.len() != 1 {
+        return Ok(None);
+    }
+    let reference = match types[0] {
+        Type::Reference(reference) if reference.mutability.is_some() => reference,
+        _ => return Ok(None),
+    };
+    let segment = match &*reference.elem {
+        Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    };
+    match segment {
+        Some(segment) if segment.ident == "Context" => match &segment.arguments {
+            PathArguments::None => Ok(Some(syn::parse_quote!(()))),
+            PathArguments::AngleBracketed(args) => match args.args.first() {
+                Some(GenericArgument::Type(state)) if args.args.len() == 1 => {
+                    Ok(Some(state.clone()))
+                }
+                _ => Err(Error::new(args.span(), "invalid state of context")),
+            },
+            PathArguments::Parenthesized(args) => {
+                Err(Error::new(args.span(), "invalid state of context"))
+            }
+        },
+        _ => Err(Error::new(
+            reference.span(),
+            "route handler can only take `&mut Context<S>` by reference",
+        )),
+    }
+}
@@ -0,0 +1,341 @@
+//! This crate provides the path rules of `roa::router`,
+//! shared by the router at runtime and by `roa-macros` at compile time.
+
+use regex::{escape, Captures, Regex};
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+/// Match pattern *{variable} or *{variable}?
+const WILDCARD: &str = r"\*\{(?P<var>\w*)\}(?P<optional>\?)?";
+
+/// Match pattern /:variable/, /:variable(regex)/ or /:variable<type>/,
+/// with an optional suffix `?`.
+const VARIABLE: &str =
+    r"/:(?P<var>\w*)(?:\((?P<regex>[^/]*)\)|<(?P<type>\w*)>)?(?P<optional>\?)?/";
+
+/// Default pattern of segment variables.
+const SEGMENT: &str = r"[^\s/]+";
+
+/// Pattern of unsigned integers.
+const UNSIGNED: &str = r"\d+";
+
+/// Pattern of signed integers.
+const SIGNED: &str = r"-?\d+";
+
+/// Pattern of uuid.
+const UUID: &str =
+    r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}";
+
+/// Error of invalid paths.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PathError {
+    /// A variable or wildcard has no name.
+    MissingVariable(String),
+
+    /// A variable appears more than once.
+    ConflictVariable { path: String, var_name: String },
+
+    /// Constraint of a variable is invalid, like an invalid regex or an unknown type.
+    InvalidConstraint {
+        path: String,
+        var_name: String,
+        reason: String,
+    },
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            PathError::MissingVariable(path) => {
+                f.write_str(&format!("missing variable on path {}", path))
+            }
+            PathError::ConflictVariable { path, var_name } => f.write_str(&format!(
+                "conflict variable `{}` on path {}",
+                var_name, path
+            )),
+            PathError::InvalidConstraint {
+                path,
+                var_name,
+                reason,
+            } => f.write_str(&format!(
+                "invalid constraint of variable `{}` on path {}: {}",
+                var_name, path, reason
+            )),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// {/path path/ /path/} => /path/
+pub fn standardize_path(raw_path: &str) -> String {
+    format!("/{}/", raw_path.trim_matches('/'))
+}
+
+/// Build pattern.
+pub fn must_build(pattern: &str) -> Regex {
+    Regex::new(pattern).unwrap_or_else(|err| {
+        panic!(
+            r#"{}
+                regex pattern {} is invalid, this is a bug of roa-path.
+                please report it to https://github.com/Hexilee/roa"#,
+            err, pattern
+        )
+    })
+}
+
+/// Check a path.
+pub fn validate(raw_path: &str) -> Result<(), PathError> {
+    path_to_regexp(&standardize_path(raw_path)).map(|_| ())
+}
+
+/// Convert a standardized path to a regex pattern and its variables,
+/// return `None` if the path is static.
+pub fn path_to_regexp(
+    path: &str,
+) -> Result<Option<(String, HashSet<String>)>, PathError> {
+    let mut pattern = escape(path);
+    let mut vars = HashSet::new();
+    let wildcard_re = must_build(WILDCARD);
+    let variable_re = must_build(VARIABLE);
+    let wildcards: Vec<Captures> = wildcard_re.captures_iter(path).collect();
+    let variable_template = path.replace('/', "//"); // to match continuous variables like /:year/:month/:day/
+    let variables: Vec<Captures> =
+        variable_re.captures_iter(&variable_template).collect();
+    // detect unclosed constraints like /:id(\d+/
+    for segment in path.split('/') {
+        if segment.starts_with(':')
+            && segment.contains(&['(', '<'][..])
+            && !variable_re.is_match(&format!("/{}/", segment))
+        {
+            return Err(PathError::InvalidConstraint {
+                path: path.to_string(),
+                var_name: segment[1..]
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect(),
+                reason: "constraint is not closed".to_string(),
+            });
+        }
+    }
+    if wildcards.is_empty() && variables.is_empty() {
+        Ok(None)
+    } else {
+        // detect variable conflicts.
+        let try_add_variable = |set: &mut HashSet<String>, variable: String| {
+            if set.insert(variable.clone()) {
+                Ok(())
+            } else {
+                Err(PathError::ConflictVariable {
+                    path: path.to_string(),
+                    var_name: variable,
+                })
+            }
+        };
+
+        // match wildcard patterns
+        for cap in wildcards {
+            let variable = &cap["var"];
+            if variable.is_empty() {
+                return Err(PathError::MissingVariable(path.to_string()));
+            }
+            let var = escape(variable);
+            let token = &cap[0];
+            let segment = format!("/{}/", token);
+            if cap.name("optional").is_none() {
+                pattern =
+                    pattern.replace(&escape(token), &format!(r"(?P<{}>\S+)", &var));
+            } else if path.contains(&segment) {
+                // an optional segment absorbs its leading slash.
+                pattern = pattern
+                    .replace(&escape(&segment), &format!(r"(?:/(?P<{}>\S+))?/", &var));
+            } else {
+                pattern =
+                    pattern.replace(&escape(token), &format!(r"(?P<{}>\S*)", &var));
+            }
+            try_add_variable(&mut vars, var)?;
+        }
+
+        // match segment variable patterns
+        for cap in variables {
+            let variable = &cap["var"];
+            if variable.is_empty() {
+                return Err(PathError::MissingVariable(path.to_string()));
+            }
+            let var = escape(variable);
+            let group = format!(r"(?P<{}>{})", &var, constraint(path, &cap)?);
+            let replacement = if cap.name("optional").is_some() {
+                // an optional segment absorbs its leading slash.
+                format!(r"(?:/{})?/", group)
+            } else {
+                format!(r"/{}/", group)
+            };
+            pattern = pattern.replace(&escape(&cap[0]), &replacement);
+            try_add_variable(&mut vars, var)?;
+        }
+        Ok(Some((pattern, vars)))
+    }
+}
+
+/// Get pattern of a segment variable by its regex or type.
+fn constraint(path: &str, cap: &Captures) -> Result<String, PathError> {
+    let invalid = |reason: String| PathError::InvalidConstraint {
+        path: path.to_string(),
+        var_name: cap["var"].to_string(),
+        reason,
+    };
+    if let Some(regex) = cap.name("regex") {
+        let re = Regex::new(regex.as_str()).map_err(|err| invalid(err.to_string()))?;
+        if re.capture_names().any(|name| name.is_some()) {
+            return Err(invalid("named groups are not allowed".to_string()));
+        }
+        return Ok(format!(r"(?:{})", regex.as_str()));
+    }
+    let pattern = match cap.name("type").map(|typ| typ.as_str()) {
+        None => SEGMENT,
+        Some("u8") | Some("u16") | Some("u32") | Some("u64") | Some("u128")
+        | Some("usize") => UNSIGNED,
+        Some("i8") | Some("i16") | Some("i32") | Some("i64") | Some("i128")
+        | Some("isize") => SIGNED,
+        Some("uuid") => UUID,
+        Some(typ) => return Err(invalid(format!("unknown type `{}`", typ))),
+    };
+    Ok(pattern.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{must_build, path_to_regexp, validate, PathError, VARIABLE, WILDCARD};
+    use test_case::test_case;
+
+    #[test_case("/:id/"; "pure dynamic")]
+    #[test_case("/user/:id/"; "static prefix")]
+    #[test_case("/user/:id/name"; "static prefix and suffix")]
+    fn var_regex_match(path: &str) {
+        let re = must_build(VARIABLE);
+        let cap = re.captures(path);
+        assert!(cap.is_some());
+        assert_eq!("id", &cap.unwrap()["var"]);
+    }
+
+    #[test_case("/:id(\\d+)/"; "regex constraint")]
+    #[test_case("/:id<u64>/"; "type constraint")]
+    #[test_case("/user/:id?/"; "optional")]
+    #[test_case("/user/:id<uuid>?/"; "optional type constraint")]
+    fn constrained_var_regex_match(path: &str) {
+        let re = must_build(VARIABLE);
+        let cap = re.captures(path);
+        assert!(cap.is_some());
+        assert_eq!("id", &cap.unwrap()["var"]);
+    }
+
+    #[test_case("/-:id/"; "invalid prefix")]
+    #[test_case("/:i-d/"; "invalid variable name")]
+    #[test_case("/:id-/"; "invalid suffix")]
+    fn var_regex_mismatch(path: &str) {
+        let re = must_build(VARIABLE);
+        let cap = re.captures(path);
+        assert!(cap.is_none());
+    }
+
+    #[test_case("*{id}"; "pure dynamic")]
+    #[test_case("user-*{id}"; "static prefix")]
+    #[test_case("user-*{id}-name"; "static prefix and suffix")]
+    fn wildcard_regex_match(path: &str) {
+        let re = must_build(WILDCARD);
+        let cap = re.captures(path);
+        assert!(cap.is_some());
+        assert_eq!("id", &cap.unwrap()["var"]);
+    }
+
+    #[test_case("*"; "no variable")]
+    #[test_case("*{-id}"; "invalid variable name")]
+    fn wildcard_regex_mismatch(path: &str) {
+        let re = must_build(WILDCARD);
+        let cap = re.captures(path);
+        assert!(cap.is_none());
+    }
+
+    #[test_case(r"/:id/" => r"/(?P<id>[^\s/]+)/"; "single variable")]
+    #[test_case(r"/:year/:month/:day/" => r"/(?P<year>[^\s/]+)/(?P<month>[^\s/]+)/(?P<day>[^\s/]+)/"; "multiple variable")]
+    #[test_case(r"*{id}" => r"(?P<id>\S+)"; "single wildcard")]
+    #[test_case(r"*{year}_*{month}_*{day}" => r"(?P<year>\S+)_(?P<month>\S+)_(?P<day>\S+)"; "multiple wildcard")]
+    #[test_case(r"/:id(\d+)/" => r"/(?P<id>(?:\d+))/"; "regex constraint")]
+    #[test_case(r"/:id<u64>/" => r"/(?P<id>\d+)/"; "unsigned constraint")]
+    #[test_case(r"/:id<i8>/" => r"/(?P<id>-?\d+)/"; "signed constraint")]
+    #[test_case(r"/user/:id?/" => r"/user(?:/(?P<id>[^\s/]+))?/"; "optional variable")]
+    #[test_case(r"/files/*{path}?/" => r"/files(?:/(?P<path>\S+))?/"; "optional wildcard segment")]
+    #[test_case(r"/files-*{path}?/" => r"/files\-(?P<path>\S*)/"; "zero or more wildcard")]
+    fn path_to_regexp_dynamic_pattern(path: &str) -> String {
+        path_to_regexp(path).unwrap().unwrap().0
+    }
+
+    #[test_case(r"/id/")]
+    #[test_case(r"/user/post/")]
+    fn path_to_regexp_static(path: &str) {
+        assert!(path_to_regexp(path).unwrap().is_none())
+    }
+
+    #[test_case(r"/:/"; "missing variable name")]
+    #[test_case(r"*{}"; "wildcard missing variable name")]
+    #[test_case(r"/:id/:id/"; "conflict variable")]
+    #[test_case(r"*{id}-*{id}"; "wildcard conflict variable")]
+    #[test_case(r"/:id/*{id}"; "mix conflict variable")]
+    #[test_case(r"/:id(\d+/"; "unclosed regex constraint")]
+    #[test_case(r"/:id([)/"; "invalid regex constraint")]
+    #[test_case(r"/:id((?P<x>\d+))/"; "named group in regex constraint")]
+    #[test_case(r"/:id<f64>/"; "unknown type constraint")]
+    #[test_case(r"/:id<u64>/:id?/"; "constraint conflict variable")]
+    fn path_to_regexp_err(path: &str) {
+        assert!(path_to_regexp(path).is_err())
+    }
+
+    #[test_case("/"; "root")]
+    #[test_case("/user/:id"; "variable")]
+    #[test_case("/:year/:month/:day"; "continuous variables")]
+    #[test_case("/static/*{path}"; "wildcard")]
+    #[test_case("/user/:id/*{path}"; "variable and wildcard")]
+    #[test_case(r"/user/:id(\d+)"; "regex constraint")]
+    #[test_case("/user/:id<uuid>?"; "optional type constraint")]
+    #[test_case("/static/*{path}?"; "optional wildcard")]
+    fn valid(path: &str) {
+        assert!(validate(path).is_ok());
+    }
+
+    #[test_case("/user/:"; "missing variable")]
+    #[test_case("/static/*{}"; "missing wildcard")]
+    #[test_case("/:id/:id"; "conflict variables")]
+    #[test_case("/:id/*{id}"; "conflict variable and wildcard")]
+    #[test_case(r"/user/:id(\d+"; "unclosed constraint")]
+    #[test_case("/user/:id([)"; "invalid regex constraint")]
+    #[test_case("/user/:id<f64>"; "unknown type constraint")]
+    fn invalid(path: &str) {
+        assert!(validate(path).is_err());
+    }
+
+    #[test]
+    fn err_to_string() {
+        assert_eq!(
+            "missing variable on path /:/",
+            validate("/:").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "conflict variable `id` on path /:id/:id/",
+            validate("/:id/:id").unwrap_err().to_string()
+        );
+        assert_eq!(
+            PathError::InvalidConstraint {
+                path: "/:id<f64>/".to_string(),
+                var_name: "id".to_string(),
+                reason: "unknown type `f64`".to_string(),
+            },
+            validate("/:id<f64>").unwrap_err()
+        );
+    }
+
+    #[should_panic]
+    #[test]
+    fn must_build_fails() {
+        must_build(r"{");
+    }
+}
@@ -0,0 +1,81 @@
+use askama::Template;
+use async_std::task::block_on;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures::io::Cursor;
+use hyper::service::Service;
+use hyper::Body;
+use roa::http::Request;
+use roa::preload::*;
+use roa::{async_trait, AddrStream, App, Context, Endpoint, Result};
+use serde::Serialize;
+
+#[derive(Serialize, Template)]
+#[template(path = "user.html")]
+struct User<'a> {
+    id: u64,
+    name: &'a str,
+}
+
+const USER: User<'static> = User {
+    id: 0,
+    name: "Hexilee",
+};
+
+struct WriteJson(usize);
+
+#[async_trait(?Send)]
+impl<'a> Endpoint<'a> for WriteJson {
+    async fn call(&'a self, ctx: &'a mut Context) -> Result {
+        for _ in 0..self.0 {
+            ctx.write_json(&USER)?;
+        }
+        Ok(())
+    }
+}
+
+struct Render(usize);
+
+#[async_trait(?Send)]
+impl<'a> Endpoint<'a> for Render {
+    async fn call(&'a self, ctx: &'a mut Context) -> Result {
+        for _ in 0..self.0 {
+            ctx.render(&USER)?;
+        }
+        Ok(())
+    }
+}
+
+fn bench_endpoint<E>(c: &mut Criterion, name: &str, endpoint: fn(usize) -> E)
+where
+    E: 'static + for<'a> Endpoint<'a>,
+{
+    let mut group = c.benchmark_group(name);
+    for writes in [1usize, 16, 256].iter() {
+        let writes = *writes;
+        let mut app = App::new().end(endpoint(writes));
+        let stream =
+            AddrStream::new(([127, 0, 0, 1], 0).into(), Cursor::new(Vec::new()));
+        let service = block_on(app.call(&stream)).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(writes), &writes, |b, _| {
+            b.iter(|| {
+                let mut service = service.clone();
+                block_on(async move {
+                    let resp = service.call(Request::new(Body::empty())).await.unwrap();
+                    hyper::body::to_bytes(resp.into_body()).await.unwrap()
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_write_json(c: &mut Criterion) {
+    bench_endpoint(c, "write json", WriteJson);
+}
+
+fn bench_render(c: &mut Criterion) {
+    bench_endpoint(c, "render template", Render);
+}
+
+criterion_group!(benches, bench_write_json, bench_render);
+criterion_main!(benches);
@@ -17,7 +17,9 @@ pub async fn write_file<S: State>(
     typ: DispositionType,
 ) -> Result {
     let path = path.as_ref();
-    ctx.resp.write_reader(File::open(path).await?);
+    let file = File::open(path).await?;
+    let len = file.metadata().await?.len();
+    ctx.resp.write_sized_reader(file, len);
 
     if let Some(filename) = path.file_name() {
         ctx.resp.headers.insert(
@@ -23,7 +23,8 @@
 
 pub use async_compression::Level;
 
-use crate::http::{header::CONTENT_ENCODING, StatusCode};
+use crate::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use crate::http::StatusCode;
 use crate::{async_trait, Context, Middleware, Next, Result, Status};
 use accept_encoding::{parse, Encoding};
 use async_compression::stream::{BrotliEncoder, GzipEncoder, ZlibEncoder, ZstdEncoder};
@@ -45,6 +46,18 @@ impl<'a, S> Middleware<'a, S> for Compress {
     #[inline]
     async fn handle(&'a self, ctx: &'a mut Context<S>, next: Next<'a>) -> Result {
         next.await?;
+        // event streams must be flushed event by event.
+        let is_event_stream = ctx
+            .resp
+            .headers
+            .get(CONTENT_TYPE)
+            .map(|content_type| {
+                content_type.as_bytes().starts_with(b"text/event-stream")
+            })
+            .unwrap_or(false);
+        if is_event_stream {
+            return Ok(());
+        }
         let level = self.0;
         let best_encoding = parse(&ctx.req.headers)
             .map_err(|err| Status::new(StatusCode::BAD_REQUEST, err, true))?;
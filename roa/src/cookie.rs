@@ -1,4 +1,4 @@
-//! This module provides a middleware `cookie_parser` and context extensions `CookieGetter` and `CookieSetter`.
+//! This module provides middleware `cookie_parser`, `CookieParser` and context extensions `CookieGetter` and `CookieSetter`.
 //!
 //! ### Example
 //!
@@ -22,14 +22,40 @@
 //! ```
 
 use crate::http::{header, StatusCode};
-use crate::{throw, Context, Next, Result};
-pub use cookie::Cookie;
+use crate::{async_trait, throw, Context, Middleware, Next, Result, Status};
+use cookie::CookieJar;
+pub use cookie::{Cookie, Key};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
 
 /// A scope to store and load variables in Context::storage.
 struct CookieScope;
 
+/// A private scope to store keys.
+struct KeyScope;
+
+/// Key of keys in Context::storage.
+const KEYS: &str = "keys";
+
+/// Keys to sign and encrypt cookies.
+struct Keys {
+    // the key to sign and encrypt cookies.
+    key: Key,
+    // keys to verify and decrypt cookies, including the current key.
+    fallbacks: Vec<Key>,
+}
+
+/// Throw a internal server error.
+#[inline]
+fn key_not_set() -> Status {
+    Status::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "middleware `CookieParser` is not set correctly with a key",
+        false,
+    )
+}
+
 /// A context extension.
 /// This extension must be used in downstream of middleware `cookier_parser`,
 /// otherwise you cannot get expected cookie.
@@ -82,6 +108,39 @@ pub trait CookieGetter {
     /// # }
     /// ```
     fn cookie(&self, name: &str) -> Option<Arc<Cookie<'static>>>;
+
+    /// Try to get a signed cookie, return `None` if it not exists or cannot be verified.
+    ///
+    /// This method must be used in downstream of middleware `CookieParser` with a key.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::cookie::{CookieParser, Key};
+    /// use roa::preload::*;
+    /// use roa::{App, Context};
+    /// use std::error::Error;
+    ///
+    /// async fn end(ctx: &mut Context) -> roa::Result {
+    ///     assert!(ctx.signed_cookie("name").is_none());
+    ///     Ok(())
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let app = App::new()
+    ///     .gate(CookieParser::new().key(Key::generate()))
+    ///     .end(end);
+    /// let (addr, server) = app.run()?;
+    /// // server.await
+    /// Ok(())
+    /// # }
+    /// ```
+    fn signed_cookie(&self, name: &str) -> Option<Cookie<'static>>;
+
+    /// Try to get a private cookie, return `None` if it not exists or cannot be decrypted.
+    ///
+    /// This method must be used in downstream of middleware `CookieParser` with a key.
+    fn private_cookie(&self, name: &str) -> Option<Cookie<'static>>;
 }
 
 /// An extension to set cookie.
@@ -108,26 +167,179 @@ pub trait CookieSetter {
     /// # }
     /// ```
     fn set_cookie(&mut self, cookie: Cookie<'_>) -> Result;
+
+    /// Set a cookie signed by the key, so the client cannot modify it.
+    ///
+    /// This method must be used in downstream of middleware `CookieParser` with a key,
+    /// otherwise it returns 500 INTERNAL SERVER ERROR.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::cookie::{Cookie, CookieParser, Key};
+    /// use roa::preload::*;
+    /// use roa::{App, Context};
+    /// use std::error::Error;
+    ///
+    /// async fn end(ctx: &mut Context) -> roa::Result {
+    ///     ctx.set_signed_cookie(Cookie::new("name", "Hexilee"))
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let app = App::new()
+    ///     .gate(CookieParser::new().key(Key::generate()))
+    ///     .end(end);
+    /// let (addr, server) = app.run()?;
+    /// // server.await
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_signed_cookie(&mut self, cookie: Cookie<'_>) -> Result;
+
+    /// Set a cookie encrypted by the key, so the client can neither read nor modify it.
+    ///
+    /// This method must be used in downstream of middleware `CookieParser` with a key,
+    /// otherwise it returns 500 INTERNAL SERVER ERROR.
+    fn set_private_cookie(&mut self, cookie: Cookie<'_>) -> Result;
+
+    /// Remove a cookie in path "/" by setting an expired one.
+    ///
+    /// Build an expired cookie by `removal` and set it to remove a cookie in other path or domain.
+    fn remove_cookie(&mut self, name: &str) -> Result;
+}
+
+/// Make a cookie expired, the client will remove it.
+///
+/// The path and domain should be the same with the cookie to remove.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::cookie::{removal, Cookie};
+/// use roa::preload::*;
+/// use roa::Context;
+///
+/// async fn logout(ctx: &mut Context) -> roa::Result {
+///     ctx.set_cookie(removal(Cookie::build("token", "").path("/api").finish()))
+/// }
+/// ```
+pub fn removal(mut cookie: Cookie<'_>) -> Cookie<'_> {
+    cookie.set_value("");
+    cookie.set_max_age(Duration::zero());
+    cookie.set_expires(OffsetDateTime::from_unix_timestamp(0));
+    cookie
 }
 
 /// A middleware to parse cookie.
 #[inline]
 pub async fn cookie_parser<S>(ctx: &mut Context<S>, next: Next<'_>) -> Result {
-    if let Some(cookies) = ctx.get(header::COOKIE) {
-        for cookie in cookies
-            .split(';')
-            .map(|cookie| cookie.trim())
-            .map(Cookie::parse_encoded)
-            .filter_map(|cookie| cookie.ok())
-            .map(|cookie| cookie.into_owned())
-            .collect::<Vec<_>>()
-            .into_iter()
-        {
-            let name = cookie.name().to_string();
-            ctx.store_scoped(CookieScope, name, cookie);
+    parse_cookies(ctx);
+    next.await
+}
+
+/// A middleware to parse cookie, with keys to sign and encrypt cookies.
+///
+/// The key signs and encrypts new cookies, fallback keys are only used to
+/// verify and decrypt cookies, so keys can be rotated without invalidating old cookies.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::cookie::{Cookie, CookieParser, Key};
+/// use roa::preload::*;
+/// use roa::{App, Context};
+/// use std::error::Error;
+///
+/// async fn end(ctx: &mut Context) -> roa::Result {
+///     if let Some(cookie) = ctx.private_cookie("user") {
+///         // cookies encrypted by old key are encrypted by new key again.
+///         ctx.set_private_cookie(cookie)?;
+///     }
+///     Ok(())
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let (old_key, new_key) = (Key::generate(), Key::generate());
+/// let app = App::new()
+///     .gate(CookieParser::new().key(new_key).fallback_keys(vec![old_key]))
+///     .end(end);
+/// let (addr, server) = app.run()?;
+/// // server.await
+/// Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct CookieParser {
+    key: Option<Key>,
+    fallbacks: Vec<Key>,
+    keys: Option<Arc<Keys>>,
+}
+
+impl CookieParser {
+    /// Construct a parser without keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the key to sign and encrypt cookies.
+    pub fn key(mut self, key: Key) -> Self {
+        self.key = Some(key);
+        self.build_keys()
+    }
+
+    /// Set fallback keys to verify and decrypt cookies, in order of preference.
+    pub fn fallback_keys(mut self, keys: impl IntoIterator<Item = Key>) -> Self {
+        self.fallbacks = keys.into_iter().collect();
+        self.build_keys()
+    }
+
+    /// Build keys shared by contexts.
+    fn build_keys(mut self) -> Self {
+        self.keys = self.key.clone().map(|key| {
+            let mut fallbacks = vec![key.clone()];
+            fallbacks.extend(self.fallbacks.iter().cloned());
+            Arc::new(Keys { key, fallbacks })
+        });
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, S> Middleware<'a, S> for CookieParser {
+    #[inline]
+    async fn handle(&'a self, ctx: &'a mut Context<S>, next: Next<'a>) -> Result {
+        parse_cookies(ctx);
+        if let Some(ref keys) = self.keys {
+            ctx.store_scoped(KeyScope, KEYS, keys.clone());
         }
+        next.await
+    }
+}
+
+/// Parse cookies in all `Cookie` headers and store them.
+pub(crate) fn parse_cookies<S>(ctx: &mut Context<S>) {
+    let cookies = ctx
+        .req
+        .headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .map(|cookie| cookie.trim())
+        .map(Cookie::parse_encoded)
+        .filter_map(|cookie| cookie.ok())
+        .map(|cookie| cookie.into_owned())
+        .collect::<Vec<_>>();
+    for cookie in cookies {
+        let name = cookie.name().to_string();
+        ctx.store_scoped(CookieScope, name, cookie);
     }
-    next.await
+}
+
+/// Load keys stored by `CookieParser`.
+fn keys<S>(ctx: &Context<S>) -> Option<Arc<Keys>> {
+    ctx.load_scoped::<KeyScope, Arc<Keys>>(KEYS)
+        .map(|keys| (*keys).clone())
 }
 
 impl<S> CookieGetter for Context<S> {
@@ -153,6 +365,26 @@ impl<S> CookieGetter for Context<S> {
     fn cookie(&self, name: &str) -> Option<Arc<Cookie<'static>>> {
         Some(self.load_scoped::<CookieScope, Cookie>(name)?.value())
     }
+
+    #[inline]
+    fn signed_cookie(&self, name: &str) -> Option<Cookie<'static>> {
+        let cookie = self.cookie(name)?;
+        keys(self)?.fallbacks.iter().find_map(|key| {
+            let mut jar = CookieJar::new();
+            jar.add_original((*cookie).clone());
+            jar.signed(key).get(name)
+        })
+    }
+
+    #[inline]
+    fn private_cookie(&self, name: &str) -> Option<Cookie<'static>> {
+        let cookie = self.cookie(name)?;
+        keys(self)?.fallbacks.iter().find_map(|key| {
+            let mut jar = CookieJar::new();
+            jar.add_original((*cookie).clone());
+            jar.private(key).get(name)
+        })
+    }
 }
 
 impl<S> CookieSetter for Context<S> {
@@ -164,13 +396,42 @@ impl<S> CookieSetter for Context<S> {
             .append(header::SET_COOKIE, cookie_value.parse()?);
         Ok(())
     }
+
+    #[inline]
+    fn set_signed_cookie(&mut self, cookie: Cookie<'_>) -> Result {
+        let keys = keys(self).ok_or_else(key_not_set)?;
+        let name = cookie.name().to_string();
+        let mut jar = CookieJar::new();
+        jar.signed(&keys.key).add(cookie.into_owned());
+        if let Some(cookie) = jar.get(&name) {
+            self.set_cookie(cookie.clone())?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn set_private_cookie(&mut self, cookie: Cookie<'_>) -> Result {
+        let keys = keys(self).ok_or_else(key_not_set)?;
+        let name = cookie.name().to_string();
+        let mut jar = CookieJar::new();
+        jar.private(&keys.key).add(cookie.into_owned());
+        if let Some(cookie) = jar.get(&name) {
+            self.set_cookie(cookie.clone())?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn remove_cookie(&mut self, name: &str) -> Result {
+        self.set_cookie(removal(Cookie::build(name, "").path("/").finish()))
+    }
 }
 
 #[cfg(all(test, feature = "tcp"))]
 mod tests {
-    use crate::cookie::{cookie_parser, Cookie};
+    use crate::cookie::{cookie_parser, Cookie, CookieParser, Key};
     use crate::http::{
-        header::{COOKIE, WWW_AUTHENTICATE},
+        header::{COOKIE, SET_COOKIE, WWW_AUTHENTICATE},
         StatusCode,
     };
     use crate::preload::*;
@@ -285,4 +546,118 @@ mod tests {
         assert_eq!(("foo%20baz"), cookies[1].value());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn signed_and_private() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(ctx: &mut Context) -> crate::Result {
+            match ctx.uri().path() {
+                "/set" => {
+                    ctx.set_signed_cookie(Cookie::new("signed", "Hexilee"))?;
+                    ctx.set_private_cookie(Cookie::new("private", "Hexilee"))?;
+                }
+                _ => {
+                    let signed = ctx.signed_cookie("signed");
+                    let private = ctx.private_cookie("private");
+                    ctx.write(format!(
+                        "{},{}",
+                        signed.as_ref().map(Cookie::value).unwrap_or_default(),
+                        private.as_ref().map(Cookie::value).unwrap_or_default(),
+                    ));
+                }
+            }
+            Ok(())
+        }
+
+        let (old_key, new_key) = (Key::generate(), Key::generate());
+        let (old, server) = App::new()
+            .gate(CookieParser::new().key(old_key.clone()))
+            .end(test)
+            .run()?;
+        spawn(server);
+        let (new, server) = App::new()
+            .gate(
+                CookieParser::new()
+                    .key(new_key)
+                    .fallback_keys(vec![old_key]),
+            )
+            .end(test)
+            .run()?;
+        spawn(server);
+        let (other, server) = App::new()
+            .gate(CookieParser::new().key(Key::generate()))
+            .end(test)
+            .run()?;
+        spawn(server);
+
+        let resp = reqwest::get(&format!("http://{}/set", old)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let cookies: Vec<String> = resp
+            .cookies()
+            .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+            .collect();
+        assert_eq!(2, cookies.len());
+        assert!(cookies[0].contains("Hexilee"));
+        assert!(!cookies[1].contains("Hexilee"));
+        let cookies = cookies.join("; ");
+
+        let client = reqwest::Client::new();
+        for addr in [old, new].iter() {
+            let resp = client
+                .get(&format!("http://{}", addr))
+                .header(COOKIE, &cookies)
+                .send()
+                .await?;
+            assert_eq!("Hexilee,Hexilee", resp.text().await?);
+        }
+
+        let resp = client
+            .get(&format!("http://{}", other))
+            .header(COOKIE, &cookies)
+            .send()
+            .await?;
+        assert_eq!(",", resp.text().await?);
+
+        let tampered = cookies.replace("Hexilee", "Admin");
+        let resp = client
+            .get(&format!("http://{}", old))
+            .header(COOKIE, &tampered)
+            .send()
+            .await?;
+        assert_eq!(",Hexilee", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_not_set() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(ctx: &mut Context) -> crate::Result {
+            assert!(ctx.signed_cookie("name").is_none());
+            ctx.set_signed_cookie(Cookie::new("name", "Hexilee"))
+        }
+        let (addr, server) = App::new().gate(cookie_parser).end(test).run()?;
+        spawn(server);
+        let resp = reqwest::Client::new()
+            .get(&format!("http://{}", addr))
+            .header(COOKIE, "name=Hexilee")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_cookie() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(ctx: &mut Context) -> crate::Result {
+            ctx.remove_cookie("name")
+        }
+        let (addr, server) = App::new().end(test).run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let cookie = resp.headers()[SET_COOKIE].to_str()?;
+        assert!(cookie.starts_with("name=;"));
+        assert!(cookie.contains("Path=/"));
+        assert!(cookie.contains("Max-Age=0"));
+        assert!(cookie.contains("Expires=Thu, 01 Jan 1970 00:00:00 GMT"));
+        Ok(())
+    }
 }
@@ -0,0 +1,152 @@
+//! This module provides a middleware `ExpectContinue`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::expect::ExpectContinue;
+//! use roa::http::StatusCode;
+//! use roa::preload::*;
+//! use roa::{throw, App, Context, Result};
+//!
+//! async fn upload(ctx: &mut Context) -> Result {
+//!     let data = ctx.read().await?;
+//!     ctx.write(format!("{} bytes received", data.len()));
+//!     Ok(())
+//! }
+//!
+//! // reject uploads of unauthorized clients before they send the body.
+//! let expect = ExpectContinue::new(|ctx: &Context| {
+//!     if ctx.get("authorization").is_none() {
+//!         throw!(StatusCode::UNAUTHORIZED)
+//!     }
+//!     Ok(())
+//! });
+//! let app = App::new().gate(expect).end(upload);
+//! ```
+
+use crate::http::header::{CONTENT_LENGTH, EXPECT};
+use crate::http::StatusCode;
+use crate::{async_trait, throw, Context, Middleware, Next, Result};
+
+/// The only expectation defined by HTTP/1.1.
+const CONTINUE: &str = "100-continue";
+
+/// A middleware to accept or reject requests with header `Expect: 100-continue`
+/// before their bodies are sent.
+///
+/// The response "100 Continue" is sent automatically when downstream reads the body,
+/// so requests rejected by the check get the final response without sending their bodies.
+///
+/// Requests with other expectations get a 417 EXPECTATION FAILED,
+/// requests without expectation are never checked.
+pub struct ExpectContinue<F> {
+    check: F,
+}
+
+impl<F> ExpectContinue<F> {
+    /// Construct a middleware by a check,
+    /// requests are rejected with the status returned by it.
+    pub fn new(check: F) -> Self {
+        Self { check }
+    }
+}
+
+/// Construct a middleware to reject requests with "Content-Length" greater than limit
+/// by a 413 PAYLOAD TOO LARGE.
+pub fn max_length<S>(
+    limit: u64,
+) -> ExpectContinue<impl 'static + Sync + Send + Fn(&Context<S>) -> Result> {
+    ExpectContinue::new(move |ctx: &Context<S>| {
+        let length = ctx
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.parse::<u64>().ok());
+        match length {
+            Some(length) if length > limit => throw!(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("payload is larger than {} bytes", limit)
+            ),
+            _ => Ok(()),
+        }
+    })
+}
+
+#[async_trait(?Send)]
+impl<'a, S, F> Middleware<'a, S> for ExpectContinue<F>
+where
+    S: 'static,
+    F: 'static + Sync + Send + Fn(&Context<S>) -> Result,
+{
+    #[inline]
+    async fn handle(&'a self, ctx: &'a mut Context<S>, next: Next<'a>) -> Result {
+        match ctx.get(EXPECT) {
+            None => (),
+            Some(expect) if expect.eq_ignore_ascii_case(CONTINUE) => (self.check)(ctx)?,
+            Some(expect) => throw!(
+                StatusCode::EXPECTATION_FAILED,
+                format!("expectation `{}` is not supported", expect)
+            ),
+        }
+        next.await
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::max_length;
+    use crate::preload::*;
+    use crate::{App, Context};
+    use async_std::net::TcpStream;
+    use async_std::task::spawn;
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    async fn upload(ctx: &mut Context) -> crate::Result {
+        let data = ctx.read().await?;
+        ctx.write(format!("{} bytes", data.len()));
+        Ok(())
+    }
+
+    /// Read until the end of a response head.
+    async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+        let mut data = Vec::new();
+        let mut buf = [0; 1024];
+        while !data.windows(4).any(|window| window == b"\r\n\r\n") {
+            let size = stream.read(&mut buf).await?;
+            if size == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..size]);
+        }
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    #[tokio::test]
+    async fn expect_continue() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new().gate(max_length(8)).end(upload).run()?;
+        spawn(server);
+
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nhost: roa\r\nexpect: 100-continue\r\ncontent-length: 16\r\n\r\n")
+            .await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nhost: roa\r\nexpect: 100-continue\r\ncontent-length: 4\r\n\r\n")
+            .await?;
+        let head = read_head(&mut stream).await?;
+        assert_eq!("HTTP/1.1 100 Continue\r\n\r\n", head);
+        stream.write_all(b"roa!").await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nhost: roa\r\nexpect: unknown\r\ncontent-length: 4\r\n\r\n")
+            .await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("HTTP/1.1 417 Expectation Failed\r\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,519 @@
+//! This module provides a trait `FromContext`, some extractors implementing it
+//! and an endpoint adapter `handler`.
+//!
+//! A handler is an async function whose arguments are extractors
+//! and whose return value implements `IntoResponse`.
+//! Each extractor will throw its own status if it fails.
+//!
+//! Handlers must be wrapped by `handler` before being passed to apis taking endpoints,
+//! like `App::end` or `roa::router::get`.
+//! They cannot implement `Endpoint` themselves, as roa-core implements it
+//! for every `Fn(&mut Context<S>) -> impl Future`, and an implementation for handlers would overlap.
+//! A conversion trait telling them apart by a marker type is not used either,
+//! because it breaks the inference of state type for plain endpoints.
+//! Route attributes with feature "macros", like `#[get("/user/:id")]`, don't need the wrapper.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::extract::{handler, Json, Path, Query};
+//! use roa::router::{get, Router, RouterError};
+//! use roa::{App, Result};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Deserialize)]
+//! struct Id {
+//!     id: u64,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Filter {
+//!     name: String,
+//! }
+//!
+//! #[derive(Deserialize, Serialize)]
+//! struct User {
+//!     name: String,
+//! }
+//!
+//! async fn get_user(Path(id): Path<Id>, Query(filter): Query<Filter>) -> String {
+//!     format!("user {} named {}", id.id, filter.name)
+//! }
+//!
+//! async fn update_user(Path(id): Path<Id>, Json(user): Json<User>) -> Result<String> {
+//!     Ok(format!("user {} is renamed to {}", id.id, user.name))
+//! }
+//!
+//! # fn main() -> std::result::Result<(), RouterError> {
+//! let router = Router::new().on(
+//!     "/user/:id",
+//!     get(handler(get_user)).put(handler(update_user)),
+//! );
+//! let app = App::new().end(router.routes("/")?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::http::StatusCode;
+use crate::response::IntoResponse;
+use crate::{async_trait, Context, Endpoint, Result};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+#[cfg(feature = "json")]
+use crate::body::PowerBody;
+#[cfg(feature = "urlencoded")]
+use crate::status;
+#[cfg(feature = "json")]
+use crate::State;
+#[cfg(any(feature = "json", feature = "urlencoded", feature = "jwt"))]
+use serde::de::DeserializeOwned;
+
+/// A trait to extract a value from context.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::extract::FromContext;
+/// use roa::{async_trait, Context, Result};
+///
+/// struct Method(String);
+///
+/// #[async_trait(?Send)]
+/// impl<S> FromContext<S> for Method {
+///     async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+///         Ok(Method(ctx.method().to_string()))
+///     }
+/// }
+/// ```
+#[async_trait(?Send)]
+pub trait FromContext<S>: Sized {
+    /// Extract a value, return a status if it fails.
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self>;
+}
+
+/// A trait for async functions taking extractors as arguments.
+///
+/// It's implemented for functions with no more than eight arguments,
+/// you don't need to implement it for your types.
+pub trait HandlerFn<Args>: 'static + Sync + Send {
+    /// Type of the returned value.
+    type Output: IntoResponse;
+
+    /// Type of the returned future.
+    type Future: Future<Output = Self::Output>;
+
+    /// Call this function.
+    fn call(&self, args: Args) -> Self::Future;
+}
+
+/// An endpoint wrapper of `HandlerFn`.
+pub struct Handler<F, Args> {
+    handler: F,
+    _args: PhantomData<fn(Args)>,
+}
+
+/// Wrap an async function taking extractors as arguments into an endpoint.
+///
+/// ```rust
+/// use roa::extract::{handler, Query};
+/// use roa::App;
+/// use std::collections::HashMap;
+///
+/// async fn echo(Query(query): Query<HashMap<String, String>>) -> String {
+///     format!("{:?}", query)
+/// }
+///
+/// let app = App::new().end(handler(echo));
+/// ```
+pub fn handler<F, Args>(handler: F) -> Handler<F, Args>
+where
+    F: HandlerFn<Args>,
+{
+    Handler {
+        handler,
+        _args: PhantomData,
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, S, F, Args> Endpoint<'a, S> for Handler<F, Args>
+where
+    S: 'a,
+    F: HandlerFn<Args>,
+    Args: 'static + FromContext<S>,
+{
+    #[inline]
+    async fn call(&'a self, ctx: &'a mut Context<S>) -> Result {
+        let args = Args::from_context(ctx).await?;
+        self.handler.call(args).await.into_response(&mut ctx.resp)
+    }
+}
+
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        impl<F, Fut, $($arg),*> HandlerFn<($($arg,)*)> for F
+        where
+            F: 'static + Sync + Send + Fn($($arg),*) -> Fut,
+            Fut: Future,
+            Fut::Output: IntoResponse,
+        {
+            type Output = Fut::Output;
+            type Future = Fut;
+
+            #[allow(non_snake_case)]
+            #[inline]
+            fn call(&self, ($($arg,)*): ($($arg,)*)) -> Fut {
+                (self)($($arg),*)
+            }
+        }
+
+        #[async_trait(?Send)]
+        impl<S, $($arg),*> FromContext<S> for ($($arg,)*)
+        where
+            $($arg: FromContext<S>),*
+        {
+            #[allow(unused_variables)]
+            #[inline]
+            async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+                Ok(($($arg::from_context(ctx).await?,)*))
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A);
+impl_handler!(A, B);
+impl_handler!(A, B, C);
+impl_handler!(A, B, C, D);
+impl_handler!(A, B, C, D, E);
+impl_handler!(A, B, C, D, E, G);
+impl_handler!(A, B, C, D, E, G, H);
+impl_handler!(A, B, C, D, E, G, H, I);
+
+macro_rules! impl_deref {
+    ($extractor:ident) => {
+        impl<T> Deref for $extractor<T> {
+            type Target = T;
+            #[inline]
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $extractor<T> {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+    };
+}
+
+/// Extract a clone of context, without request and response.
+#[async_trait(?Send)]
+impl<S: Clone> FromContext<S> for Context<S> {
+    #[inline]
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+        Ok(ctx.clone())
+    }
+}
+
+/// An extractor to deserialize router variables,
+/// throw 400 BAD REQUEST if it fails.
+#[cfg(all(feature = "router", feature = "urlencoded"))]
+#[cfg_attr(
+    feature = "docs",
+    doc(cfg(all(feature = "router", feature = "urlencoded")))
+)]
+pub struct Path<T>(pub T);
+
+#[cfg(all(feature = "router", feature = "urlencoded"))]
+impl_deref!(Path);
+
+#[cfg(all(feature = "router", feature = "urlencoded"))]
+#[async_trait(?Send)]
+impl<S, T> FromContext<S> for Path<T>
+where
+    T: DeserializeOwned,
+{
+    #[inline]
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+        let params = serde_urlencoded::to_string(crate::router::params(ctx))?;
+        serde_urlencoded::from_str(&params)
+            .map(Path)
+            .map_err(|err| status!(StatusCode::BAD_REQUEST, err))
+    }
+}
+
+/// An extractor to deserialize query string,
+/// throw 400 BAD REQUEST if it fails.
+#[cfg(feature = "urlencoded")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "urlencoded")))]
+pub struct Query<T>(pub T);
+
+#[cfg(feature = "urlencoded")]
+impl_deref!(Query);
+
+#[cfg(feature = "urlencoded")]
+#[async_trait(?Send)]
+impl<S, T> FromContext<S> for Query<T>
+where
+    T: DeserializeOwned,
+{
+    #[inline]
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+        serde_urlencoded::from_str(ctx.uri().query().unwrap_or(""))
+            .map(Query)
+            .map_err(|err| status!(StatusCode::BAD_REQUEST, err))
+    }
+}
+
+/// An extractor to deserialize request body as "json",
+/// throw 400 BAD REQUEST if it fails.
+#[cfg(feature = "json")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "json")))]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl_deref!(Json);
+
+#[cfg(feature = "json")]
+#[async_trait(?Send)]
+impl<S, T> FromContext<S> for Json<T>
+where
+    S: State,
+    T: 'static + DeserializeOwned,
+{
+    #[inline]
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+        ctx.read_json().await.map(Json)
+    }
+}
+
+/// An extractor to deserialize request body as "urlencoded form",
+/// throw 400 BAD REQUEST if it fails.
+#[cfg(feature = "urlencoded")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "urlencoded")))]
+pub struct Form<T>(pub T);
+
+#[cfg(feature = "urlencoded")]
+impl_deref!(Form);
+
+#[cfg(feature = "urlencoded")]
+#[async_trait(?Send)]
+impl<S, T> FromContext<S> for Form<T>
+where
+    S: State,
+    T: 'static + DeserializeOwned,
+{
+    #[inline]
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+        crate::body::PowerBody::read_form(ctx).await.map(Form)
+    }
+}
+
+/// An extractor to deserialize claims of json web token,
+/// throw 401 UNAUTHORIZED if it fails.
+///
+/// It must be used in downstream of middleware `roa::jwt::guard`.
+#[cfg(feature = "jwt")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "jwt")))]
+pub struct Claims<T>(pub T);
+
+#[cfg(feature = "jwt")]
+impl_deref!(Claims);
+
+#[cfg(feature = "jwt")]
+#[async_trait(?Send)]
+impl<S, T> FromContext<S> for Claims<T>
+where
+    T: 'static + DeserializeOwned,
+{
+    #[inline]
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+        use crate::jwt::{set_www_authenticate, JwtVerifier};
+        let value: serde_json::Value = ctx.claims()?;
+        match serde_json::from_value(value) {
+            Ok(claims) => Ok(Claims(claims)),
+            Err(err) => {
+                set_www_authenticate(ctx);
+                Err(crate::Status::new(StatusCode::UNAUTHORIZED, err, true))
+            }
+        }
+    }
+}
+
+/// An extractor to get a typed header,
+/// throw 400 BAD REQUEST if it not exists or fails to decode.
+pub struct TypedHeader<T>(pub T);
+
+impl_deref!(TypedHeader);
+
+#[async_trait(?Send)]
+impl<S, T> FromContext<S> for TypedHeader<T>
+where
+    T: crate::header::Header,
+{
+    #[inline]
+    async fn from_context(ctx: &mut Context<S>) -> Result<Self> {
+        use crate::header::HeaderGetter;
+        ctx.must_header().map(TypedHeader)
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "tcp",
+    feature = "router",
+    feature = "json",
+    feature = "urlencoded",
+    feature = "jwt"
+))]
+mod tests {
+    use super::{handler, Claims, Json, Path, Query, TypedHeader};
+    use crate::header::ContentType;
+    use crate::http::header::AUTHORIZATION;
+    use crate::http::StatusCode;
+    use crate::jwt::{guard, DecodingKey};
+    use crate::preload::*;
+    use crate::router::{get, Router};
+    use crate::App;
+    use async_std::task::spawn;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde::{Deserialize, Serialize};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[derive(Deserialize)]
+    struct Id {
+        id: u64,
+    }
+
+    #[derive(Deserialize)]
+    struct Filter {
+        name: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        exp: u64,
+    }
+
+    const SECRET: &[u8] = b"123456";
+
+    #[tokio::test]
+    async fn path_and_query() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(Path(id): Path<Id>, Query(filter): Query<Filter>) -> String {
+            format!("{}:{}", id.id, filter.name)
+        }
+        let router = Router::new().on("/user/:id", get(handler(test)));
+        let (addr, server) = App::new().end(router.routes("/")?).run()?;
+        spawn(server);
+
+        let resp = reqwest::get(&format!("http://{}/user/1?name=Hexilee", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("1:Hexilee", resp.text().await?);
+
+        // invalid router variable
+        let resp = reqwest::get(&format!("http://{}/user/x?name=Hexilee", addr)).await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+        // missing query
+        let resp = reqwest::get(&format!("http://{}/user/1", addr)).await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_and_header() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(
+            TypedHeader(content_type): TypedHeader<ContentType>,
+            Json(user): Json<User>,
+        ) -> crate::Result<String> {
+            assert_eq!(ContentType::json(), content_type);
+            Ok(user.name)
+        }
+        let (addr, server) = App::new().end(handler(test)).run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .json(&User {
+                name: "Hexilee".to_string(),
+                exp: 0,
+            })
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hexilee", resp.text().await?);
+
+        // missing content type
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .body(r#"{"name": "Hexilee", "exp": 0}"#)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+        // invalid json
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .header(crate::http::header::CONTENT_TYPE, "application/json")
+            .body(r#"{"name": "Hexilee"}"#)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claims() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Deserialize)]
+        struct Admin {
+            #[allow(dead_code)]
+            admin: bool,
+        }
+
+        async fn user(Claims(user): Claims<User>) -> String {
+            user.name
+        }
+
+        async fn admin(_admin: Claims<Admin>) {}
+
+        let router = Router::new()
+            .gate(guard(DecodingKey::from_secret(SECRET)))
+            .on("/user", handler(user))
+            .on("/admin", handler(admin));
+        let (addr, server) = App::new().end(router.routes("/")?).run()?;
+        spawn(server);
+        let token = encode(
+            &Header::default(),
+            &User {
+                name: "Hexilee".to_string(),
+                exp: (SystemTime::now() + Duration::from_secs(86400))
+                    .duration_since(UNIX_EPOCH)?
+                    .as_secs(),
+            },
+            &EncodingKey::from_secret(SECRET),
+        )?;
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}/user", addr))
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hexilee", resp.text().await?);
+
+        let resp = client
+            .get(&format!("http://{}/admin", addr))
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
+        Ok(())
+    }
+}
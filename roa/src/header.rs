@@ -0,0 +1,193 @@
+//! This module provides context extensions `HeaderGetter` and `HeaderSetter`
+//! to read and write typed headers, based on crate [headers](https://github.com/hyperium/headers).
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::header::{ContentType, IfNoneMatch};
+//! use roa::preload::*;
+//! use roa::{App, Context, Result};
+//!
+//! async fn end(ctx: &mut Context) -> Result {
+//!     if let Some(if_none_match) = ctx.header::<IfNoneMatch>() {
+//!         println!("if-none-match: {:?}", if_none_match);
+//!     }
+//!     let content_type: ContentType = ctx.must_header()?;
+//!     ctx.resp.set_header(content_type);
+//!     Ok(())
+//! }
+//!
+//! let app = App::new().end(end);
+//! ```
+
+pub use headers::*;
+
+use crate::http::StatusCode;
+use crate::{Context, Response, Result, Status};
+
+/// A context extension to get typed request headers.
+pub trait HeaderGetter {
+    /// Try to get and decode a typed header, return `None` if it not exists or fails to decode.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::header::UserAgent;
+    /// use roa::preload::*;
+    /// use roa::{Context, Result};
+    ///
+    /// async fn get(ctx: &mut Context) -> Result {
+    ///     if let Some(user_agent) = ctx.header::<UserAgent>() {
+    ///         println!("user agent: {}", user_agent);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    fn header<H: Header>(&self) -> Option<H>;
+
+    /// Must get and decode a typed header,
+    /// throw 400 BAD REQUEST with the header name if it not exists or fails to decode.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::header::{authorization::Bearer, Authorization};
+    /// use roa::preload::*;
+    /// use roa::{Context, Result};
+    ///
+    /// async fn get(ctx: &mut Context) -> Result {
+    ///     let auth: Authorization<Bearer> = ctx.must_header()?;
+    ///     println!("token: {}", auth.0.token());
+    ///     Ok(())
+    /// }
+    /// ```
+    fn must_header<H: Header>(&self) -> Result<H>;
+}
+
+/// A response extension to set typed headers.
+pub trait HeaderSetter {
+    /// Encode a typed header and insert it into response headers,
+    /// the previous values of this header will be replaced.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::header::ContentType;
+    /// use roa::preload::*;
+    /// use roa::{Context, Result};
+    ///
+    /// async fn get(ctx: &mut Context) -> Result {
+    ///     ctx.resp.set_header(ContentType::json());
+    ///     ctx.resp.write(r#"{"name": "Hexilee"}"#);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn set_header<H: Header>(&mut self, header: H);
+}
+
+impl<S> HeaderGetter for Context<S> {
+    #[inline]
+    fn header<H: Header>(&self) -> Option<H> {
+        self.req.headers.typed_get()
+    }
+
+    #[inline]
+    fn must_header<H: Header>(&self) -> Result<H> {
+        match self.req.headers.typed_try_get() {
+            Ok(Some(header)) => Ok(header),
+            Ok(None) => Err(Status::new(
+                StatusCode::BAD_REQUEST,
+                format!("header `{}` is required", H::name()),
+                true,
+            )),
+            Err(err) => Err(Status::new(
+                StatusCode::BAD_REQUEST,
+                format!("{}\nheader `{}` is invalid", err, H::name()),
+                true,
+            )),
+        }
+    }
+}
+
+impl HeaderSetter for Response {
+    #[inline]
+    fn set_header<H: Header>(&mut self, header: H) {
+        self.headers.typed_insert(header)
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::{ContentType, IfModifiedSince, IfNoneMatch};
+    use crate::http::header::{CONTENT_TYPE, IF_MODIFIED_SINCE};
+    use crate::http::StatusCode;
+    use crate::preload::*;
+    use crate::{App, Context};
+    use async_std::task::spawn;
+
+    #[tokio::test]
+    async fn header() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(ctx: &mut Context) -> crate::Result {
+            assert!(ctx.header::<IfNoneMatch>().is_none());
+            assert_eq!(ContentType::json(), ctx.header::<ContentType>().unwrap());
+            Ok(())
+        }
+        let (addr, server) = App::new().end(test).run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(CONTENT_TYPE, "application/json")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn must_header() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(ctx: &mut Context) -> crate::Result {
+            let _: IfModifiedSince = ctx.must_header()?;
+            Ok(())
+        }
+        let (addr, server) = App::new().end(test).run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client.get(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert_eq!("header `if-modified-since` is required", resp.text().await?);
+
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(IF_MODIFIED_SINCE, "yesterday")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert!(resp
+            .text()
+            .await?
+            .ends_with("header `if-modified-since` is invalid"));
+
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(IF_MODIFIED_SINCE, "Sat, 29 Oct 1994 19:43:31 GMT")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_header() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(ctx: &mut Context) -> crate::Result {
+            ctx.resp.set_header(ContentType::text_utf8());
+            Ok(())
+        }
+        let (addr, server) = App::new().end(test).run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/plain; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+        Ok(())
+    }
+}
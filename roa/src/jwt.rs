@@ -87,7 +87,7 @@ lazy_static::lazy_static!(
 
 /// A function to set value of WWW_AUTHENTICATE.
 #[inline]
-fn set_www_authenticate<S>(ctx: &mut Context<S>) {
+pub(crate) fn set_www_authenticate<S>(ctx: &mut Context<S>) {
     ctx.resp
         .headers
         .insert(WWW_AUTHENTICATE, INVALID_TOKEN.clone());
@@ -20,6 +20,10 @@ pub mod tls;
 #[cfg_attr(feature = "docs", doc(cfg(feature = "websocket")))]
 pub mod websocket;
 
+#[cfg(feature = "sse")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "sse")))]
+pub mod sse;
+
 #[cfg(feature = "cookies")]
 #[cfg_attr(feature = "docs", doc(cfg(feature = "cookies")))]
 pub mod cookie;
@@ -28,21 +32,31 @@ pub mod cookie;
 #[cfg_attr(feature = "docs", doc(cfg(feature = "jwt")))]
 pub mod jwt;
 
+#[cfg(feature = "session")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "session")))]
+pub mod session;
+
 #[cfg(feature = "compress")]
 #[cfg_attr(feature = "docs", doc(cfg(feature = "compress")))]
 pub mod compress;
 
 pub mod body;
 pub mod cors;
+pub mod expect;
+pub mod extract;
 pub mod forward;
+pub mod header;
 pub mod logger;
+pub mod method_override;
 pub mod query;
+pub mod response;
 pub mod stream;
 
 /// Reexport all extension traits.
 pub mod preload {
     pub use crate::body::PowerBody;
     pub use crate::forward::Forward;
+    pub use crate::header::{HeaderGetter, HeaderSetter};
     pub use crate::query::Query;
 
     #[cfg(feature = "tcp")]
@@ -59,6 +73,9 @@ pub mod preload {
     #[cfg(feature = "jwt")]
     pub use crate::jwt::JwtVerifier;
 
+    #[cfg(feature = "session")]
+    pub use crate::session::SessionGetter;
+
     #[cfg(feature = "router")]
     pub use crate::router::RouterParam;
 }
@@ -0,0 +1,320 @@
+//! This module provides a middleware `MethodOverride`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::method_override::MethodOverride;
+//! use roa::router::{put, Router};
+//! use roa::{App, Context, Result};
+//!
+//! async fn update(ctx: &mut Context) -> Result {
+//!     Ok(())
+//! }
+//!
+//! async fn remove(ctx: &mut Context) -> Result {
+//!     Ok(())
+//! }
+//!
+//! # fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+//! // `<form method="post" action="/post?_method=DELETE">` hits `remove`.
+//! let router = Router::new().on("/post", put(update).delete(remove));
+//! let app = App::new()
+//!     .gate(MethodOverride::new())
+//!     .end(router.routes("/")?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::http::header::{HeaderName, CONTENT_TYPE};
+use crate::http::{Method, StatusCode};
+use crate::{async_trait, throw, Context, Middleware, Next, Request, Result};
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use hyper::Body;
+use std::collections::HashSet;
+use std::iter::FromIterator;
+use url::form_urlencoded::parse;
+
+/// Name of the field in query or form.
+const FIELD: &str = "_method";
+
+/// Content type of forms.
+const FORM: &str = "application/x-www-form-urlencoded";
+
+/// Default length of form prefixes searched for the target method, 64 KiB.
+const DEFAULT_LIMIT: u64 = 64 * 1024;
+
+/// A middleware to override the method of POST requests.
+///
+/// The target method is taken from, in order:
+/// - header `X-HTTP-Method-Override`;
+/// - query field `_method`;
+/// - form field `_method` in the first 64 KiB of the body by default,
+///   the body is read and restored for downstream.
+///
+/// Requests with a target method not in the allowed set get a 400 BAD REQUEST.
+/// Requests with other methods are never overridden.
+#[derive(Debug, Clone)]
+pub struct MethodOverride {
+    methods: HashSet<Method>,
+    header: bool,
+    query: bool,
+    form: bool,
+    limit: u64,
+}
+
+impl MethodOverride {
+    /// Construct a middleware allowing PUT, PATCH and DELETE from all sources.
+    pub fn new() -> Self {
+        Self {
+            methods: HashSet::from_iter(vec![
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ]),
+            header: true,
+            query: true,
+            form: true,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    /// Set the allowed target methods.
+    pub fn methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods = HashSet::from_iter(methods);
+        self
+    }
+
+    /// Whether to take target method from header `X-HTTP-Method-Override`.
+    pub fn header(mut self, enable: bool) -> Self {
+        self.header = enable;
+        self
+    }
+
+    /// Whether to take target method from query field `_method`.
+    pub fn query(mut self, enable: bool) -> Self {
+        self.query = enable;
+        self
+    }
+
+    /// Whether to take target method from form field `_method`.
+    pub fn form(mut self, enable: bool) -> Self {
+        self.form = enable;
+        self
+    }
+
+    /// Set the max length of the form prefix searched for field `_method` in bytes,
+    /// 64 KiB by default. Larger forms are passed to downstream untouched.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Find the target method.
+    async fn target<S>(&self, ctx: &mut Context<S>) -> Result<Option<String>> {
+        if self.header {
+            let name = HeaderName::from_static("x-http-method-override");
+            if let Some(value) = ctx.get(name) {
+                return Ok(Some(value.to_string()));
+            }
+        }
+        if self.query {
+            if let Some(value) = ctx.uri().query().and_then(field) {
+                return Ok(Some(value));
+            }
+        }
+        let is_form = ctx
+            .get(CONTENT_TYPE)
+            .map(|content_type| content_type.starts_with(FORM))
+            .unwrap_or(false);
+        if self.form && is_form {
+            let mut body = ctx.req.stream();
+            let mut data = Vec::new();
+            let mut finished = false;
+            while data.len() as u64 <= self.limit {
+                match body.next().await {
+                    Some(chunk) => data.extend_from_slice(&chunk?),
+                    None => {
+                        finished = true;
+                        break;
+                    }
+                }
+            }
+            let value = if finished {
+                std::str::from_utf8(&data).ok().and_then(field)
+            } else {
+                // only complete fields in the prefix are searched.
+                let prefix = &data[..self.limit as usize];
+                let end = prefix.iter().rposition(|byte| *byte == b'&').unwrap_or(0);
+                std::str::from_utf8(&prefix[..end]).ok().and_then(field)
+            };
+            let body = if finished {
+                Body::from(data)
+            } else {
+                let prefix = stream::once(async move { Ok(Bytes::from(data)) });
+                Body::wrap_stream(prefix.chain(body))
+            };
+            restore_body(ctx, body);
+            return Ok(value);
+        }
+        Ok(None)
+    }
+}
+
+impl Default for MethodOverride {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get field `_method` from urlencoded data.
+fn field(data: &str) -> Option<String> {
+    parse(data.as_bytes())
+        .find(|(key, _)| key == FIELD)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Put the consumed body back to request.
+fn restore_body<S>(ctx: &mut Context<S>, body: Body) {
+    let mut req = crate::http::Request::new(body);
+    *req.method_mut() = ctx.req.method.clone();
+    *req.uri_mut() = std::mem::take(&mut ctx.req.uri);
+    *req.version_mut() = ctx.req.version;
+    *req.headers_mut() = std::mem::take(&mut ctx.req.headers);
+    ctx.req = Request::from(req);
+}
+
+#[async_trait(?Send)]
+impl<'a, S> Middleware<'a, S> for MethodOverride {
+    #[inline]
+    async fn handle(&'a self, ctx: &'a mut Context<S>, next: Next<'a>) -> Result {
+        if ctx.method() != Method::POST {
+            return next.await;
+        }
+        if let Some(target) = self.target(ctx).await? {
+            let method = match target.to_uppercase().parse() {
+                Ok(method) if self.methods.contains(&method) => method,
+                _ => throw!(
+                    StatusCode::BAD_REQUEST,
+                    format!("method override `{}` is not allowed", target)
+                ),
+            };
+            ctx.req.method = method;
+        }
+        next.await
+    }
+}
+
+#[cfg(all(test, feature = "tcp", feature = "router", feature = "urlencoded"))]
+mod tests {
+    use super::MethodOverride;
+    use crate::http::{Method, StatusCode};
+    use crate::preload::*;
+    use crate::router::{post, put, Router};
+    use crate::{App, Context};
+    use async_std::task::spawn;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Post {
+        title: String,
+    }
+
+    async fn update(ctx: &mut Context) -> crate::Result {
+        let post: Post = ctx.read_form().await?;
+        ctx.resp.write(format!("update {}", post.title));
+        Ok(())
+    }
+
+    async fn create(ctx: &mut Context) -> crate::Result {
+        let post: Post = ctx.read_form().await?;
+        ctx.resp.write(format!("create {}", post.title));
+        Ok(())
+    }
+
+    async fn remove(ctx: &mut Context) -> crate::Result {
+        ctx.resp.write("remove");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn method_override() -> Result<(), Box<dyn std::error::Error>> {
+        let router = Router::new()
+            .on("/post", put(update).delete(remove))
+            .on("/post/create", post(create).put(update));
+        let app = App::new()
+            .gate(
+                MethodOverride::new()
+                    .methods(vec![Method::PUT, Method::DELETE])
+                    .limit(64),
+            )
+            .end(router.routes("/")?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/post", addr);
+
+        let resp = client
+            .post(&url)
+            .header("x-http-method-override", "delete")
+            .send()
+            .await?;
+        assert_eq!("remove", resp.text().await?);
+
+        let resp = client
+            .post(&format!("{}?_method=DELETE", url))
+            .send()
+            .await?;
+        assert_eq!("remove", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .form(&[("title", "roa"), ("_method", "PUT")])
+            .send()
+            .await?;
+        assert_eq!("update roa", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .form(&[("_method", "PUT"), ("title", "roa".repeat(20).as_str())])
+            .send()
+            .await?;
+        assert_eq!(
+            "update ".to_string() + &"roa".repeat(20),
+            resp.text().await?
+        );
+
+        // large forms without override are passed to downstream.
+        let title = "roa".repeat(20_000);
+        let resp = client
+            .post(&format!("{}/create", url))
+            .form(&[("title", title.as_str())])
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(format!("create {}", title), resp.text().await?);
+
+        // fields after the limit are ignored.
+        let resp = client
+            .post(&format!("{}/create", url))
+            .form(&[("title", "roa".repeat(20).as_str()), ("_method", "PUT")])
+            .send()
+            .await?;
+        assert!(resp.text().await?.starts_with("create "));
+
+        let resp = client
+            .post(&url)
+            .header("x-http-method-override", "PATCH")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+        let resp = client
+            .get(&url)
+            .header("x-http-method-override", "DELETE")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, resp.status());
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+//! This module provides a trait `IntoResponse` for values returned by handlers,
+//! some response wrappers implementing it and an endpoint adapter `respond`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::http::StatusCode;
+//! use roa::response::{respond, Html, Redirect};
+//! use roa::router::{get, Router, RouterError};
+//! use roa::{App, Context};
+//!
+//! async fn index(_ctx: &mut Context) -> Html<&'static str> {
+//!     Html("<h1>Hello, World!</h1>")
+//! }
+//!
+//! async fn created(_ctx: &mut Context) -> (StatusCode, String) {
+//!     (StatusCode::CREATED, "created".to_string())
+//! }
+//!
+//! async fn home(_ctx: &mut Context) -> Redirect {
+//!     Redirect::to("/")
+//! }
+//!
+//! # fn main() -> Result<(), RouterError> {
+//! let router = Router::new()
+//!     .on("/", get(respond(index)))
+//!     .on("/home", get(respond(home)))
+//!     .on("/created", get(respond(created)));
+//! let app = App::new().end(router.routes("/")?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::http::header::{HeaderValue, CONTENT_TYPE, LOCATION};
+use crate::http::StatusCode;
+use crate::{async_trait, Context, Endpoint, Response, Result};
+use bytes::Bytes;
+use futures::Stream as FutureStream;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+
+#[cfg(feature = "json")]
+pub use crate::extract::Json;
+#[cfg(feature = "template")]
+use askama::Template;
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+/// A trait to write a value into response.
+pub trait IntoResponse {
+    /// Write status, headers and body into response.
+    fn into_response(self, resp: &mut Response) -> Result;
+}
+
+/// Write nothing.
+impl IntoResponse for () {
+    #[inline]
+    fn into_response(self, _resp: &mut Response) -> Result {
+        Ok(())
+    }
+}
+
+/// Write the value if `Ok`, otherwise throw the status.
+impl<T> IntoResponse for Result<T>
+where
+    T: IntoResponse,
+{
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        self?.into_response(resp)
+    }
+}
+
+/// Write as "text/plain; charset=utf-8".
+impl IntoResponse for String {
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        write_with(resp, self, "text/plain; charset=utf-8");
+        Ok(())
+    }
+}
+
+/// Write as "text/plain; charset=utf-8".
+impl IntoResponse for &'static str {
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        write_with(resp, self, "text/plain; charset=utf-8");
+        Ok(())
+    }
+}
+
+/// Write as "application/octet-stream".
+impl IntoResponse for Bytes {
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        write_with(resp, self, "application/octet-stream");
+        Ok(())
+    }
+}
+
+/// Write as "application/octet-stream".
+impl IntoResponse for Vec<u8> {
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        write_with(resp, self, "application/octet-stream");
+        Ok(())
+    }
+}
+
+/// Set status, then write the value.
+impl<T> IntoResponse for (StatusCode, T)
+where
+    T: IntoResponse,
+{
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        let (status, value) = self;
+        resp.status = status;
+        value.into_response(resp)
+    }
+}
+
+/// Serialize the value and write as "application/json".
+#[cfg(feature = "json")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "json")))]
+impl<T> IntoResponse for Json<T>
+where
+    T: Serialize,
+{
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        write_with(resp, serde_json::to_vec(&self.0)?, "application/json");
+        Ok(())
+    }
+}
+
+/// A wrapper to write html as "text/html; charset=utf-8".
+pub struct Html<T>(pub T);
+
+impl<T> IntoResponse for Html<T>
+where
+    T: Into<Bytes>,
+{
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        write_with(resp, self.0, "text/html; charset=utf-8");
+        Ok(())
+    }
+}
+
+/// A wrapper to render a template, based on [askama](https://github.com/djc/askama),
+/// and write it as "text/html; charset=utf-8".
+#[cfg(feature = "template")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "template")))]
+pub struct Render<T>(pub T);
+
+#[cfg(feature = "template")]
+impl<T> IntoResponse for Render<T>
+where
+    T: Template,
+{
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        write_with(resp, self.0.render()?, "text/html; charset=utf-8");
+        Ok(())
+    }
+}
+
+/// A wrapper to write a stream of bytes as "application/octet-stream".
+pub struct Stream<T>(pub T);
+
+impl<T> IntoResponse for Stream<T>
+where
+    T: 'static + Sync + Send + FutureStream<Item = io::Result<Bytes>>,
+{
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        resp.write_stream(self.0);
+        resp.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        Ok(())
+    }
+}
+
+/// A redirection, set status and "Location".
+pub struct Redirect {
+    status: StatusCode,
+    location: String,
+}
+
+impl Redirect {
+    /// Redirect with 303 SEE OTHER.
+    #[inline]
+    pub fn to(location: impl ToString) -> Self {
+        Self::with_status(StatusCode::SEE_OTHER, location)
+    }
+
+    /// Redirect with 307 TEMPORARY REDIRECT.
+    #[inline]
+    pub fn temporary(location: impl ToString) -> Self {
+        Self::with_status(StatusCode::TEMPORARY_REDIRECT, location)
+    }
+
+    /// Redirect with 308 PERMANENT REDIRECT.
+    #[inline]
+    pub fn permanent(location: impl ToString) -> Self {
+        Self::with_status(StatusCode::PERMANENT_REDIRECT, location)
+    }
+
+    #[inline]
+    fn with_status(status: StatusCode, location: impl ToString) -> Self {
+        Self {
+            status,
+            location: location.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for Redirect {
+    #[inline]
+    fn into_response(self, resp: &mut Response) -> Result {
+        resp.headers.insert(LOCATION, self.location.parse()?);
+        resp.status = self.status;
+        Ok(())
+    }
+}
+
+/// A trait for async functions taking context and returning `IntoResponse`.
+///
+/// You don't need to implement it for your types.
+pub trait RespondFn<'a, S, T>: 'static + Sync + Send {
+    /// Type of the returned future.
+    type Future: 'a + Future<Output = T>;
+
+    /// Call this function.
+    fn call(&self, ctx: &'a mut Context<S>) -> Self::Future;
+}
+
+impl<'a, S, T, F, Fut> RespondFn<'a, S, T> for F
+where
+    S: 'a,
+    F: 'static + Sync + Send + Fn(&'a mut Context<S>) -> Fut,
+    Fut: 'a + Future<Output = T>,
+{
+    type Future = Fut;
+
+    #[inline]
+    fn call(&self, ctx: &'a mut Context<S>) -> Fut {
+        (self)(ctx)
+    }
+}
+
+/// An endpoint wrapper of `RespondFn`.
+pub struct Respond<F, T> {
+    respond: F,
+    _output: PhantomData<fn() -> T>,
+}
+
+/// Wrap an async function taking context and returning `IntoResponse` into an endpoint.
+///
+/// ```rust
+/// use roa::response::respond;
+/// use roa::{App, Context};
+///
+/// async fn hello(_ctx: &mut Context) -> &'static str {
+///     "Hello, World!"
+/// }
+///
+/// let app = App::new().end(respond(hello));
+/// ```
+pub fn respond<F, T>(respond: F) -> Respond<F, T> {
+    Respond {
+        respond,
+        _output: PhantomData,
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, S, F, T> Endpoint<'a, S> for Respond<F, T>
+where
+    S: 'a,
+    F: for<'b> RespondFn<'b, S, T>,
+    T: 'static + IntoResponse,
+{
+    #[inline]
+    async fn call(&'a self, ctx: &'a mut Context<S>) -> Result {
+        let value = self.respond.call(ctx).await;
+        value.into_response(&mut ctx.resp)
+    }
+}
+
+#[inline]
+fn write_with(resp: &mut Response, data: impl Into<Bytes>, content_type: &'static str) {
+    resp.write(data);
+    resp.headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+}
+
+#[cfg(all(
+    test,
+    feature = "tcp",
+    feature = "router",
+    feature = "json",
+    feature = "template"
+))]
+mod tests {
+    use super::{respond, Html, Json, Redirect, Render, Stream};
+    use crate::http::header::{CONTENT_TYPE, LOCATION};
+    use crate::http::StatusCode;
+    use crate::preload::*;
+    use crate::router::{get, Router};
+    use crate::{App, Context};
+    use askama::Template;
+    use async_std::task::spawn;
+    use bytes::Bytes;
+    use futures::stream;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Template)]
+    #[template(path = "user.html")]
+    struct User {
+        id: u64,
+        name: String,
+    }
+
+    fn user() -> User {
+        User {
+            id: 0,
+            name: "Hexilee".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn text_and_bytes() -> Result<(), Box<dyn std::error::Error>> {
+        let router = Router::new()
+            .on("/text", get(respond(|_ctx: &mut Context| async { "text" })))
+            .on(
+                "/bytes",
+                get(respond(|_ctx: &mut Context| async {
+                    Bytes::from_static(b"bytes")
+                })),
+            )
+            .on(
+                "/stream",
+                get(respond(|_ctx: &mut Context| async {
+                    Stream(stream::iter(vec![
+                        Ok(Bytes::from_static(b"str")),
+                        Ok(Bytes::from_static(b"eam")),
+                    ]))
+                })),
+            );
+        let (addr, server) = App::new().end(router.routes("/")?).run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/text", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/plain; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("text", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/bytes", addr)).await?;
+        assert_eq!("application/octet-stream", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("bytes", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/stream", addr)).await?;
+        assert_eq!("application/octet-stream", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("stream", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_html_and_render() -> Result<(), Box<dyn std::error::Error>> {
+        async fn json(_ctx: &mut Context) -> (StatusCode, Json<User>) {
+            (StatusCode::CREATED, Json(user()))
+        }
+        async fn html(_ctx: &mut Context) -> Html<&'static str> {
+            Html("<h1>Hello, World!</h1>")
+        }
+        async fn render(_ctx: &mut Context) -> crate::Result<Render<User>> {
+            Ok(Render(user()))
+        }
+        let router = Router::new()
+            .on("/json", get(respond(json)))
+            .on("/html", get(respond(html)))
+            .on("/render", get(respond(render)));
+        let (addr, server) = App::new().end(router.routes("/")?).run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/json", addr)).await?;
+        assert_eq!(StatusCode::CREATED, resp.status());
+        assert_eq!("application/json", resp.headers()[CONTENT_TYPE]);
+        assert_eq!(user(), resp.json().await?);
+
+        let resp = reqwest::get(&format!("http://{}/html", addr)).await?;
+        assert_eq!("text/html; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("<h1>Hello, World!</h1>", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/render", addr)).await?;
+        assert_eq!("text/html; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+        assert!(resp.text().await?.contains("Hexilee"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redirect() -> Result<(), Box<dyn std::error::Error>> {
+        async fn test(_ctx: &mut Context) -> Redirect {
+            Redirect::temporary("/login")
+        }
+        let (addr, server) = App::new().end(respond(test)).run()?;
+        spawn(server);
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let resp = client.get(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::TEMPORARY_REDIRECT, resp.status());
+        assert_eq!("/login", resp.headers()[LOCATION]);
+        Ok(())
+    }
+}
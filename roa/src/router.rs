@@ -46,10 +46,23 @@
 //! }
 //! ```
 //!
+//! ### Path syntax
+//!
+//! - `:var` matches a non-empty segment.
+//! - `:var(regex)` matches a segment by a regex, like `/user/:id(\d+)`.
+//! - `:var<type>` matches a segment by a built-in type,
+//!   integer types like `u64` or `i32`, and `uuid` are supported.
+//! - `*{var}` matches one or more characters, including `/`.
+//! - A trailing `?` makes a whole-segment variable or wildcard optional, like `/user/:id?` or `/files/*{path}?`,
+//!   or makes a wildcard inside a segment match zero or more characters.
+//!
+//! Absent optional variables cannot be got by `RouterParam`.
 
 mod endpoints;
 mod err;
+mod normalize;
 mod path;
+mod reload;
 
 #[doc(inline)]
 pub use endpoints::*;
@@ -57,21 +70,75 @@ pub use endpoints::*;
 #[doc(inline)]
 pub use err::RouterError;
 
-use crate::http::StatusCode;
+#[doc(inline)]
+pub use normalize::{Normalization, TrailingSlash};
+
+#[doc(inline)]
+pub use reload::ReloadableRouter;
+
+#[cfg(feature = "macros")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "macros")))]
+pub use roa_macros::{
+    connect, delete, get, head, options, patch, post, put, routes, trace,
+};
+
+use crate::http::header::LOCATION;
+use crate::http::{StatusCode, Uri};
 use crate::{
     async_trait, throw, Boxed, Context, Endpoint, EndpointExt, Middleware,
     MiddlewareExt, Result, Shared, Status, Variable,
 };
 use err::Conflict;
-use path::{join_path, standardize_path, Path, RegexPath};
+use path::{
+    decode_segments, has_trailing_slash, join_path, standardize_path, Path, RegexPath,
+};
 use percent_encoding::percent_decode_str;
 use radix_trie::Trie;
+use std::collections::HashSet;
 use std::convert::AsRef;
 use std::result::Result as StdResult;
 
 /// A private scope to store and load variables in Context::storage.
 struct RouterScope;
 
+/// A private scope to store all router variables in Context::storage.
+struct ParamsScope;
+
+/// Key of all router variables in `ParamsScope`.
+const PARAMS: &str = "params";
+
+/// Key of the unmatched path in `ParamsScope`.
+const UNMATCHED: &str = "unmatched";
+
+/// Key of the mount path in `ParamsScope`.
+const MOUNT_PATH: &str = "mount_path";
+
+/// Key of the original path before mounting in `ParamsScope`.
+const ORIGINAL_PATH: &str = "original_path";
+
+/// Get all router variables as (name, value) pairs.
+#[inline]
+pub(crate) fn params<S>(ctx: &Context<S>) -> Vec<(String, String)> {
+    ctx.load_scoped::<ParamsScope, Vec<(String, String)>>(PARAMS)
+        .map(|params| (*params).clone())
+        .unwrap_or_default()
+}
+
+/// Load an optional path in `ParamsScope`.
+#[inline]
+fn load_path<S>(ctx: &Context<S>, key: &'static str) -> Option<String> {
+    ctx.load_scoped::<ParamsScope, Option<String>>(key)
+        .and_then(|path| (*path).clone())
+}
+
+/// Store a router variable.
+fn store_param<S>(ctx: &mut Context<S>, name: &str, value: String) {
+    let mut all_params = params(ctx);
+    all_params.push((name.to_string(), value.clone()));
+    ctx.store_scoped(ParamsScope, PARAMS, all_params);
+    ctx.store_scoped(RouterScope, name.to_string(), value);
+}
+
 /// A context extension.
 /// This extension must be used in `Router`,
 /// otherwise you cannot get expected router parameters.
@@ -137,18 +204,80 @@ pub trait RouterParam {
     ///
     /// ```
     fn param<'a>(&self, name: &'a str) -> Option<Variable<'a, String>>;
+
+    /// Get the unmatched path in a fallback, relative to the prefix of the fallback,
+    /// return `None` if it's not in a fallback.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::{Router, RouterParam};
+    /// use roa::{App, Context, Result};
+    ///
+    /// async fn not_found(ctx: &mut Context) -> Result {
+    ///     let path = ctx.unmatched_path().unwrap().to_string();
+    ///     ctx.resp.write(format!("`{}` is not found", path));
+    ///     Ok(())
+    /// }
+    ///
+    /// # fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    /// let router = Router::new().fallback(not_found);
+    /// let app = App::new().end(router.routes("/api")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn unmatched_path(&self) -> Option<Variable<'static, String>>;
+
+    /// Get the prefix stripped by `Router::mount`, including prefixes of outer mounts,
+    /// return `None` if it's not in a mounted endpoint.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::{Router, RouterParam};
+    /// use roa::{App, Context, Result};
+    ///
+    /// async fn graphql(ctx: &mut Context) -> Result {
+    ///     // "/api/graphql" for request "/api/graphql/schema"
+    ///     let mount_path = ctx.mount_path().unwrap();
+    ///     // "/api/graphql/schema"
+    ///     let original_path = ctx.original_path().unwrap();
+    ///     // "/schema"
+    ///     let path = ctx.uri().path().to_string();
+    ///     ctx.resp.write(format!("{} {} {}", mount_path, original_path, path));
+    ///     Ok(())
+    /// }
+    ///
+    /// # fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    /// let router = Router::new().mount("/graphql", graphql);
+    /// let app = App::new().end(router.routes("/api")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn mount_path(&self) -> Option<String>;
+
+    /// Get the request path before the outermost `Router::mount` stripped it,
+    /// return `None` if it's not in a mounted endpoint.
+    fn original_path(&self) -> Option<String>;
 }
 
 /// A builder of `RouteTable`.
 pub struct Router<S> {
     middleware: Shared<S>,
     endpoints: Vec<(String, Boxed<S>)>,
+    mounts: Vec<(String, Boxed<S>)>,
+    fallbacks: Vec<(String, Boxed<S>)>,
 }
 
 /// An endpoint to route request by uri path.
 pub struct RouteTable<S> {
-    static_route: Trie<String, Boxed<S>>,
-    dynamic_route: Vec<(RegexPath, Boxed<S>)>,
+    normalization: Normalization,
+    // endpoints with flags of trailing slash.
+    static_route: Trie<String, Vec<(bool, Boxed<S>)>>,
+    dynamic_route: Vec<(RegexPath, bool, Boxed<S>)>,
+    // mounts and fallbacks sorted by length of prefixes in descending order.
+    mounts: Vec<(String, Boxed<S>)>,
+    fallbacks: Vec<(String, Boxed<S>)>,
 }
 
 impl<S> Router<S>
@@ -160,6 +289,8 @@ where
         Self {
             middleware: ().shared(),
             endpoints: Vec::new(),
+            mounts: Vec::new(),
+            fallbacks: Vec::new(),
         }
     }
 
@@ -174,6 +305,74 @@ where
         self
     }
 
+    /// Mount an endpoint under a prefix.
+    ///
+    /// Requests under the prefix matching no route are passed to the endpoint,
+    /// with the prefix stripped from `ctx.uri()`, the uri is restored after the endpoint returns.
+    /// The stripped prefix and the original path can be got by `RouterParam`.
+    /// The prefix must be static, building a route table with variables in it fails.
+    ///
+    /// ```rust
+    /// use roa::router::{get, Router, RouterError};
+    /// use roa::{App, Context, Result};
+    ///
+    /// async fn user(ctx: &mut Context) -> Result {
+    ///     // "/user" for request "/v2/user"
+    ///     let path = ctx.uri().path().to_string();
+    ///     ctx.resp.write(path);
+    ///     Ok(())
+    /// }
+    ///
+    /// # fn main() -> std::result::Result<(), RouterError> {
+    /// let v2 = Router::new().on("/user", get(user)).routes("/")?;
+    /// let router = Router::new().mount("/v2", v2);
+    /// let app = App::new().end(router.routes("/")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mount(
+        mut self,
+        prefix: &'static str,
+        endpoint: impl for<'a> Endpoint<'a, S>,
+    ) -> Self {
+        self.mounts
+            .push((prefix.to_string(), self.register(endpoint)));
+        self
+    }
+
+    /// Set a fallback endpoint, it will be called if no route under the prefix of this router matches.
+    ///
+    /// Fallbacks of included routers work under their own prefixes,
+    /// the fallback with the longest matching prefix will be called.
+    ///
+    /// ```rust
+    /// use roa::body::{DispositionType, PowerBody};
+    /// use roa::http::StatusCode;
+    /// use roa::router::{get, Router, RouterError};
+    /// use roa::{App, Context, Result};
+    ///
+    /// async fn index(ctx: &mut Context) -> Result {
+    ///     ctx.write_file("assets/welcome.html", DispositionType::Inline).await
+    /// }
+    ///
+    /// async fn api_not_found(ctx: &mut Context) -> Result {
+    ///     ctx.resp.status = StatusCode::NOT_FOUND;
+    ///     ctx.write_json(&"not found")
+    /// }
+    ///
+    /// # fn main() -> std::result::Result<(), RouterError> {
+    /// let api = Router::new().fallback(api_not_found);
+    /// let router = Router::new().include("/api", api).fallback(index);
+    /// let app = App::new().end(router.routes("/")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fallback(mut self, endpoint: impl for<'a> Endpoint<'a, S>) -> Self {
+        self.fallbacks
+            .push((String::new(), self.register(endpoint)));
+        self
+    }
+
     /// Chain an endpoint to Router::middleware.
     fn register(&self, endpoint: impl for<'a> Endpoint<'a, S>) -> Boxed<S> {
         self.middleware.clone().end(endpoint).boxed()
@@ -185,6 +384,14 @@ where
             self.endpoints
                 .push((join_path([prefix, path.as_str()]), self.register(endpoint)))
         }
+        for (path, endpoint) in router.mounts {
+            self.mounts
+                .push((join_path([prefix, path.as_str()]), self.register(endpoint)))
+        }
+        for (path, endpoint) in router.fallbacks {
+            self.fallbacks
+                .push((join_path([prefix, path.as_str()]), self.register(endpoint)))
+        }
         self
     }
 
@@ -193,19 +400,40 @@ where
         let Self {
             middleware,
             endpoints,
+            mounts,
+            fallbacks,
         } = self;
         Self {
             middleware: middleware.chain(next).shared(),
             endpoints,
+            mounts,
+            fallbacks,
         }
     }
 
     /// Build RouteTable with path prefix.
     pub fn routes(self, prefix: &'static str) -> StdResult<RouteTable<S>, RouterError> {
-        let mut route_table = RouteTable::default();
+        self.routes_with(prefix, Normalization::default())
+    }
+
+    /// Build RouteTable with path prefix and normalization policy.
+    pub fn routes_with(
+        self,
+        prefix: &'static str,
+        normalization: Normalization,
+    ) -> StdResult<RouteTable<S>, RouterError> {
+        let mut route_table = RouteTable::new(normalization);
         for (raw_path, endpoint) in self.endpoints {
             route_table.insert(join_path([prefix, raw_path.as_str()]), endpoint)?;
         }
+        for (raw_path, endpoint) in self.mounts {
+            route_table
+                .insert_mount(join_path([prefix, raw_path.as_str()]), endpoint)?;
+        }
+        for (raw_path, endpoint) in self.fallbacks {
+            route_table
+                .insert_fallback(join_path([prefix, raw_path.as_str()]), endpoint)?;
+        }
         Ok(route_table)
     }
 }
@@ -214,10 +442,13 @@ impl<S> RouteTable<S>
 where
     S: 'static,
 {
-    fn new() -> Self {
+    fn new(normalization: Normalization) -> Self {
         Self {
+            normalization,
             static_route: Trie::new(),
             dynamic_route: Vec::new(),
+            mounts: Vec::new(),
+            fallbacks: Vec::new(),
         }
     }
 
@@ -227,16 +458,238 @@ where
         raw_path: impl AsRef<str>,
         endpoint: Boxed<S>,
     ) -> StdResult<(), RouterError> {
+        let trailing_slash = has_trailing_slash(raw_path.as_ref());
+        let lenient = self.normalization.trailing_slash == TrailingSlash::Lenient;
         match raw_path.as_ref().parse()? {
-            Path::Static(path) => {
-                if self.static_route.insert(path.clone(), endpoint).is_some() {
+            Path::Static(mut path) => {
+                if self.normalization.case_insensitive {
+                    path = path.to_lowercase();
+                }
+                if self.static_route.get(&path).is_none() {
+                    self.static_route.insert(path.clone(), Vec::new());
+                }
+                let endpoints = self.static_route.get_mut(&path).unwrap();
+                if endpoints
+                    .iter()
+                    .any(|(slash, _)| lenient || *slash == trailing_slash)
+                {
                     return Err(Conflict::Path(path).into());
                 }
+                endpoints.push((trailing_slash, endpoint));
+            }
+            Path::Dynamic(mut regex_path) => {
+                if self.normalization.case_insensitive {
+                    regex_path = regex_path.case_insensitive();
+                }
+                self.dynamic_route
+                    .push((regex_path, trailing_slash, endpoint))
             }
-            Path::Dynamic(regex_path) => self.dynamic_route.push((regex_path, endpoint)),
         }
         Ok(())
     }
+
+    /// Insert fallback to table.
+    fn insert_fallback(
+        &mut self,
+        raw_prefix: impl AsRef<str>,
+        endpoint: Boxed<S>,
+    ) -> StdResult<(), RouterError> {
+        let prefix = self.standardize_prefix(raw_prefix.as_ref())?;
+        if self.fallbacks.iter().any(|(path, _)| *path == prefix) {
+            return Err(Conflict::Fallback(prefix).into());
+        }
+        insert_by_prefix(&mut self.fallbacks, prefix, endpoint);
+        Ok(())
+    }
+
+    /// Insert mounted endpoint to table.
+    fn insert_mount(
+        &mut self,
+        raw_prefix: impl AsRef<str>,
+        endpoint: Boxed<S>,
+    ) -> StdResult<(), RouterError> {
+        let prefix = self.standardize_prefix(raw_prefix.as_ref())?;
+        if self.mounts.iter().any(|(path, _)| *path == prefix) {
+            return Err(Conflict::Mount(prefix).into());
+        }
+        insert_by_prefix(&mut self.mounts, prefix, endpoint);
+        Ok(())
+    }
+
+    /// {"" /} => /, {/path path/ /path/} => /path/
+    ///
+    /// Prefixes are matched literally, so variables and wildcards are rejected.
+    fn standardize_prefix(&self, raw_prefix: &str) -> StdResult<String, RouterError> {
+        let prefix = match raw_prefix.trim_matches('/') {
+            "" => "/".to_string(),
+            path => format!("/{}/", path),
+        };
+        if let Path::Dynamic(_) = prefix.parse()? {
+            return Err(RouterError::DynamicPrefix(prefix));
+        }
+        if self.normalization.case_insensitive {
+            Ok(prefix.to_lowercase())
+        } else {
+            Ok(prefix)
+        }
+    }
+
+    /// Decode request path by normalization policy.
+    fn decode(&self, raw_path: &str) -> Result<String> {
+        let decoded = if self.normalization.raw_segments {
+            decode_segments(raw_path).map_err(|err| err.to_string())
+        } else {
+            percent_decode_str(raw_path)
+                .decode_utf8()
+                .map(|path| path.to_string())
+                .map_err(|err| err.to_string())
+        };
+        decoded.map_err(|err| {
+            Status::new(
+                StatusCode::BAD_REQUEST,
+                format!("{}\npath `{}` is not a valid utf-8 string", err, raw_path),
+                true,
+            )
+        })
+    }
+
+    /// Whether a route is registered on a standardized path.
+    fn contains(&self, path: &str) -> bool {
+        let key = if self.normalization.case_insensitive {
+            path.to_lowercase()
+        } else {
+            path.to_string()
+        };
+        self.static_route.get(&key).is_some()
+            || self.dynamic_route.iter().any(|(regexp_path, _, _)| {
+                if self.normalization.case_insensitive {
+                    regexp_path.raw.eq_ignore_ascii_case(path)
+                } else {
+                    regexp_path.raw == path
+                }
+            })
+    }
+
+    /// Whether a route is disabled by `ReloadableRouter`.
+    fn disabled(&self, disabled: &HashSet<String>, route: &str) -> bool {
+        if self.normalization.case_insensitive {
+            disabled.iter().any(|path| path.eq_ignore_ascii_case(route))
+        } else {
+            disabled.contains(route)
+        }
+    }
+
+    /// Redirect to the other form of trailing slash.
+    ///
+    /// The location is built from the standardized path with the mount path prepended,
+    /// so it always starts with exactly one slash and cannot point to another host.
+    fn redirect(&self, ctx: &mut Context<S>, status: StatusCode) -> Result {
+        let mount_path = load_path(ctx, MOUNT_PATH).unwrap_or_default();
+        let uri = ctx.uri();
+        let path = join_path([mount_path.as_str(), uri.path().trim_matches('/')]);
+        let mut location = format!("/{}", path);
+        if !has_trailing_slash(uri.path()) && !path.is_empty() {
+            location.push('/');
+        }
+        if let Some(query) = uri.query() {
+            location.push('?');
+            location.push_str(query);
+        }
+        ctx.resp.headers.insert(LOCATION, location.parse()?);
+        throw!(status)
+    }
+}
+
+/// Insert an endpoint to a list sorted by length of prefixes in descending order.
+fn insert_by_prefix<S>(
+    list: &mut Vec<(String, Boxed<S>)>,
+    prefix: String,
+    endpoint: Boxed<S>,
+) {
+    let index = list
+        .iter()
+        .position(|(path, _)| path.len() < prefix.len())
+        .unwrap_or(list.len());
+    list.insert(index, (prefix, endpoint));
+}
+
+/// Get the rest of a path after skipping the segments of a standardized prefix.
+///
+/// An encoded slash `%2F` separates segments as well if `encoded_slash` is true,
+/// so the path is split in the same way as it was matched.
+fn strip_prefix<'a>(path: &'a str, prefix: &str, encoded_slash: bool) -> &'a str {
+    let depth = prefix.matches('/').count() - 1;
+    let path = path.trim_start_matches('/');
+    let bytes = path.as_bytes();
+    let mut count = 0;
+    let mut index = 0;
+    while count < depth && index < bytes.len() {
+        let separator = if bytes[index] == b'/' {
+            1
+        } else if encoded_slash
+            && bytes[index] == b'%'
+            && path
+                .get(index + 1..index + 3)
+                .filter(|hex| hex.eq_ignore_ascii_case("2f"))
+                .is_some()
+        {
+            3
+        } else {
+            index += 1;
+            continue;
+        };
+        count += 1;
+        index += separator;
+    }
+    if count < depth {
+        ""
+    } else {
+        &path[index..]
+    }
+}
+
+/// Call a mounted endpoint with the prefix stripped from uri.
+async fn call_mounted<S>(
+    ctx: &mut Context<S>,
+    prefix: &str,
+    end: &Boxed<S>,
+    encoded_slash: bool,
+) -> Result
+where
+    S: 'static,
+{
+    let raw_path = ctx.uri().path().to_string();
+    let rest = strip_prefix(&raw_path, prefix, encoded_slash);
+    let stripped = &raw_path[..raw_path.len() - rest.len()];
+    let mut parts = ctx.uri().clone().into_parts();
+    let path_and_query = match ctx.uri().query() {
+        Some(query) => format!("/{}?{}", rest, query),
+        None => format!("/{}", rest),
+    };
+    parts.path_and_query = Some(path_and_query.parse()?);
+    let uri = Uri::from_parts(parts)?;
+
+    let outer_mount = load_path(ctx, MOUNT_PATH);
+    let outer_original = load_path(ctx, ORIGINAL_PATH);
+    let mount_path = match format!(
+        "{}{}",
+        outer_mount.as_deref().unwrap_or("").trim_end_matches('/'),
+        stripped.trim_end_matches('/')
+    ) {
+        ref path if path.is_empty() => "/".to_string(),
+        path => path,
+    };
+    let original = outer_original.clone().unwrap_or_else(|| raw_path.clone());
+    ctx.store_scoped(ParamsScope, MOUNT_PATH, Some(mount_path));
+    ctx.store_scoped(ParamsScope, ORIGINAL_PATH, Some(original));
+    let original_uri = std::mem::replace(&mut ctx.req.uri, uri);
+
+    let result = end.call(ctx).await;
+
+    ctx.req.uri = original_uri;
+    ctx.store_scoped(ParamsScope, MOUNT_PATH, outer_mount);
+    ctx.store_scoped(ParamsScope, ORIGINAL_PATH, outer_original);
+    result
 }
 
 impl<S> Default for Router<S>
@@ -253,7 +706,7 @@ where
     S: 'static,
 {
     fn default() -> Self {
-        Self::new()
+        Self::new(Normalization::default())
     }
 }
 
@@ -264,38 +717,97 @@ where
 {
     #[inline]
     async fn call(&'a self, ctx: &'a mut Context<S>) -> Result {
-        let uri = ctx.uri();
+        self.call_with(ctx, &HashSet::new()).await
+    }
+}
+
+impl<S> RouteTable<S>
+where
+    S: 'static,
+{
+    /// Handle a request as if the disabled routes did not exist.
+    async fn call_with(
+        &self,
+        ctx: &mut Context<S>,
+        disabled: &HashSet<String>,
+    ) -> Result {
+        let raw_path = ctx.uri().path();
+        let trailing_slash = has_trailing_slash(raw_path);
         // standardize path
-        let path =
-            standardize_path(&percent_decode_str(uri.path()).decode_utf8().map_err(
-                |err| {
-                    Status::new(
-                        StatusCode::BAD_REQUEST,
-                        format!(
-                            "{}\npath `{}` is not a valid utf-8 string",
-                            err,
-                            uri.path()
-                        ),
-                        true,
-                    )
-                },
-            )?);
+        let path = standardize_path(&self.decode(raw_path)?);
+        let lenient = self.normalization.trailing_slash == TrailingSlash::Lenient;
+        // whether any route matches the other form of trailing slash.
+        let mut mismatched = false;
 
         // search static routes
-        if let Some(end) = self.static_route.get(&path) {
-            return end.call(ctx).await;
+        let key = if self.normalization.case_insensitive {
+            path.to_lowercase()
+        } else {
+            path.clone()
+        };
+        let static_endpoints = self
+            .static_route
+            .get(&key)
+            .filter(|_| !self.disabled(disabled, &key));
+        if let Some(endpoints) = static_endpoints {
+            for (slash, end) in endpoints.iter() {
+                if lenient || *slash == trailing_slash {
+                    return end.call(ctx).await;
+                }
+                mismatched = true;
+            }
         }
 
         // search dynamic routes
-        for (regexp_path, end) in self.dynamic_route.iter() {
+        for (regexp_path, slash, end) in self.dynamic_route.iter() {
+            if self.disabled(disabled, &regexp_path.raw) {
+                continue;
+            }
             if let Some(cap) = regexp_path.re.captures(&path) {
+                if !lenient && *slash != trailing_slash {
+                    mismatched = true;
+                    continue;
+                }
+                let mut all_params = params(ctx);
                 for var in regexp_path.vars.iter() {
-                    ctx.store_scoped(
-                        RouterScope,
-                        var.to_string(),
-                        cap[var.as_str()].to_string(),
-                    );
+                    // optional variables may be absent.
+                    if let Some(value) = cap.name(var) {
+                        let value = if self.normalization.raw_segments {
+                            percent_decode_str(value.as_str())
+                                .decode_utf8_lossy()
+                                .to_string()
+                        } else {
+                            value.as_str().to_string()
+                        };
+                        all_params.push((var.to_string(), value.clone()));
+                        ctx.store_scoped(RouterScope, var.to_string(), value);
+                    }
                 }
+                ctx.store_scoped(ParamsScope, PARAMS, all_params);
+                return end.call(ctx).await;
+            }
+        }
+
+        if mismatched {
+            if let Some(status) = self.normalization.redirect_status() {
+                return self.redirect(ctx, status);
+            }
+        }
+
+        // search mounts
+        for (prefix, end) in self.mounts.iter() {
+            if key.starts_with(prefix.as_str()) {
+                let encoded_slash = !self.normalization.raw_segments;
+                return call_mounted(ctx, prefix, end, encoded_slash).await;
+            }
+        }
+
+        // search fallbacks
+        for (prefix, end) in self.fallbacks.iter() {
+            if key.starts_with(prefix.as_str()) {
+                let unmatched = strip_prefix(path.trim_end_matches('/'), prefix, false);
+                let unmatched = format!("/{}", unmatched);
+                ctx.store_scoped(ParamsScope, UNMATCHED, unmatched);
                 return end.call(ctx).await;
             }
         }
@@ -320,11 +832,27 @@ impl<S> RouterParam for Context<S> {
     fn param<'a>(&self, name: &'a str) -> Option<Variable<'a, String>> {
         self.load_scoped::<RouterScope, String>(name)
     }
+
+    #[inline]
+    fn unmatched_path(&self) -> Option<Variable<'static, String>> {
+        self.load_scoped::<ParamsScope, String>(UNMATCHED)
+    }
+
+    #[inline]
+    fn mount_path(&self) -> Option<String> {
+        load_path(self, MOUNT_PATH)
+    }
+
+    #[inline]
+    fn original_path(&self) -> Option<String> {
+        load_path(self, ORIGINAL_PATH)
+    }
 }
 
 #[cfg(all(test, feature = "tcp"))]
 mod tests {
-    use super::Router;
+    use super::{Normalization, Router, RouterParam, TrailingSlash};
+    use crate::http::header::LOCATION;
     use crate::http::StatusCode;
     use crate::tcp::Listener;
     use crate::{App, Context, Next, Status};
@@ -366,6 +894,313 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn optional_and_constrained() -> Result<(), Box<dyn std::error::Error>> {
+        async fn user(ctx: &mut Context) -> Result<(), Status> {
+            let id = ctx.param("id").map(|id| id.to_string());
+            ctx.resp.write(id.unwrap_or_else(|| "all".to_string()));
+            Ok(())
+        }
+        let router = Router::new().on("/user/:id<u64>?", user);
+        let app = App::new().end(router.routes("/")?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/user/1", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("1", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/user", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("all", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/user/abc", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn strict_trailing_slash() -> Result<(), Box<dyn std::error::Error>> {
+        async fn user(ctx: &mut Context) -> Result<(), Status> {
+            ctx.resp.write("user");
+            Ok(())
+        }
+        async fn users(ctx: &mut Context) -> Result<(), Status> {
+            ctx.resp.write("users");
+            Ok(())
+        }
+        let router = Router::new()
+            .on("/user", user)
+            .on("/user/", users)
+            .on("/post/:id/", test);
+        let normalization = Normalization::new().trailing_slash(TrailingSlash::Strict);
+        let app = App::new()
+            .gate(gate)
+            .end(router.routes_with("/", normalization)?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/user", addr)).await?;
+        assert_eq!("user", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/user/", addr)).await?;
+        assert_eq!("users", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/post/1/", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = reqwest::get(&format!("http://{}/post/1", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redirect_trailing_slash() -> Result<(), Box<dyn std::error::Error>> {
+        let router = Router::new().on("/user/", test).on("/post/:id", test);
+        let normalization =
+            Normalization::new().trailing_slash(TrailingSlash::PermanentRedirect);
+        let app = App::new()
+            .gate(gate)
+            .end(router.routes_with("/api", normalization)?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let resp = client
+            .get(&format!("http://{}/api/user?name=Hexilee", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::PERMANENT_REDIRECT, resp.status());
+        assert_eq!("/api/user/?name=Hexilee", resp.headers()[LOCATION]);
+        let resp = client
+            .get(&format!("http://{}/api/post/1/", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::PERMANENT_REDIRECT, resp.status());
+        assert_eq!("/api/post/1", resp.headers()[LOCATION]);
+        let resp = client
+            .get(&format!("http://{}/api/post/1", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redirect_to_same_host() -> Result<(), Box<dyn std::error::Error>> {
+        let router = Router::new().on("/:name", test);
+        let normalization =
+            Normalization::new().trailing_slash(TrailingSlash::MovedPermanently);
+        let app = App::new()
+            .gate(gate)
+            .end(router.routes_with("/", normalization)?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let resp = client
+            .get(&format!("http://{}//evil.com/?a=1", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::MOVED_PERMANENTLY, resp.status());
+        assert_eq!("/evil.com?a=1", resp.headers()[LOCATION]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redirect_in_mount() -> Result<(), Box<dyn std::error::Error>> {
+        let normalization =
+            Normalization::new().trailing_slash(TrailingSlash::PermanentRedirect);
+        let v2 = Router::new()
+            .on("/user", test)
+            .routes_with("/", normalization)?;
+        let router = Router::new().gate(gate).mount("/v2", v2);
+        let app = App::new().end(router.routes("/api")?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let resp = client
+            .get(&format!("http://{}/api/v2/user/", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::PERMANENT_REDIRECT, resp.status());
+        assert_eq!("/api/v2/user", resp.headers()[LOCATION]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn case_insensitive_and_raw_segments() -> Result<(), Box<dyn std::error::Error>>
+    {
+        async fn file(ctx: &mut Context) -> Result<(), Status> {
+            let name = ctx.must_param("name")?.to_string();
+            ctx.resp.write(name);
+            Ok(())
+        }
+        let router = Router::new().on("/File/:name", file).on("/User", test);
+        let normalization = Normalization::new()
+            .case_insensitive(true)
+            .raw_segments(true);
+        let app = App::new()
+            .gate(gate)
+            .end(router.routes_with("/", normalization)?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/uSeR", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = reqwest::get(&format!("http://{}/file/A%2Fb%25", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("A/b%", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/file/a/b", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fallback() -> Result<(), Box<dyn std::error::Error>> {
+        async fn root(ctx: &mut Context) -> Result<(), Status> {
+            let path = ctx.unmatched_path().unwrap().to_string();
+            ctx.resp.write(format!("root:{}", path));
+            Ok(())
+        }
+        async fn api(ctx: &mut Context) -> Result<(), Status> {
+            let path = ctx.unmatched_path().unwrap().to_string();
+            ctx.resp.status = StatusCode::NOT_FOUND;
+            ctx.resp.write(format!("api:{}", path));
+            Ok(())
+        }
+        let user_router = Router::new().on("/:id", test);
+        let api_router = Router::new().include("/user", user_router).fallback(api);
+        let router = Router::new()
+            .gate(gate)
+            .include("/api", api_router)
+            .fallback(root);
+        let app = App::new().end(router.routes("/")?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/api/user/0", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = reqwest::get(&format!("http://{}/api/post/0/", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        assert_eq!("api:/post/0", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/api", addr)).await?;
+        assert_eq!("api:/", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/apis/user", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("root:/apis/user", resp.text().await?);
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_fallback() {
+        let router = Router::new()
+            .fallback(test)
+            .include("/", Router::new().fallback(test));
+        assert!(router.routes("/").is_err());
+    }
+
+    #[tokio::test]
+    async fn mount() -> Result<(), Box<dyn std::error::Error>> {
+        async fn restore(ctx: &mut Context, next: Next<'_>) -> Result<(), Status> {
+            let path = ctx.uri().path().to_string();
+            next.await?;
+            assert_eq!(path, ctx.uri().path());
+            assert!(ctx.mount_path().is_none());
+            Ok(())
+        }
+        async fn inner(ctx: &mut Context) -> Result<(), Status> {
+            let uri = ctx.uri().to_string();
+            let mount_path = ctx.mount_path().unwrap();
+            let original_path = ctx.original_path().unwrap();
+            ctx.resp
+                .write(format!("{} {} {}", uri, mount_path, original_path));
+            Ok(())
+        }
+        let graphql = Router::new().on("/schema", inner).routes("/")?;
+        let v2 = Router::new()
+            .on("/user/:id", inner)
+            .mount("/graphql", graphql)
+            .routes("/")?;
+        let router = Router::new()
+            .gate(gate)
+            .on("/v2/static", test)
+            .include("/api", Router::new().mount("/v2", v2))
+            .mount("/", inner);
+        let app = App::new().gate(restore).end(router.routes("/")?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/api/v2/user/0?a=1", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("/user/0?a=1 /api/v2 /api/v2/user/0", resp.text().await?);
+        let resp =
+            reqwest::get(&format!("http://{}/api/v2/graphql/schema/", addr)).await?;
+        assert_eq!(
+            "/schema/ /api/v2/graphql /api/v2/graphql/schema/",
+            resp.text().await?
+        );
+        let resp = reqwest::get(&format!("http://{}/api/v2/post", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        let resp = reqwest::get(&format!("http://{}/other", addr)).await?;
+        assert_eq!("/other / /other", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/v2/static", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mount_encoded_slash() -> Result<(), Box<dyn std::error::Error>> {
+        async fn inner(ctx: &mut Context) -> Result<(), Status> {
+            let path = ctx.uri().path().to_string();
+            let mount_path = ctx.mount_path().unwrap();
+            ctx.resp.write(format!("{} {}", path, mount_path));
+            Ok(())
+        }
+        let decoded = Router::new().mount("/a/b", inner).routes("/")?;
+        let raw = Router::new()
+            .mount("/a%2Fb", inner)
+            .routes_with("/", Normalization::new().raw_segments(true))?;
+        let router = Router::new().mount("/decoded", decoded).mount("/raw", raw);
+        let app = App::new().end(router.routes("/")?);
+        let (addr, server) = app.run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/decoded/a%2Fb/x", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("/x /decoded/a%2Fb", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/raw/a%2Fb/x", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("/x /raw/a%2Fb", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/raw/a/b/x", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_mount() {
+        let router = Router::new()
+            .mount("/v2", test)
+            .include("/v2", Router::new().mount("/", test));
+        assert!(router.routes("/").is_err());
+    }
+
+    #[test]
+    fn dynamic_prefix() {
+        assert!(Router::new().mount("/:id", test).routes("/").is_err());
+        assert!(Router::new()
+            .mount("/user/:id<u32>", test)
+            .routes("/")
+            .is_err());
+        assert!(Router::new()
+            .include("/*{path}", Router::new().fallback(test))
+            .routes("/")
+            .is_err());
+        assert!(Router::new()
+            .mount("/v2", test)
+            .routes("/:version")
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_constraint() {
+        let router = Router::new().on("/user/:id([)", test);
+        assert!(router.routes("/").is_err());
+    }
+
     #[test]
     fn conflict_path() -> Result<(), Box<dyn std::error::Error>> {
         let evil_router = Router::new().on("/endpoint", test);
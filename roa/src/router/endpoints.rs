@@ -1,5 +1,7 @@
 mod dispatcher;
 mod guard;
+mod hosts;
+mod versioned;
 
 use crate::http::{Method, StatusCode};
 use crate::{throw, Result};
@@ -17,3 +19,7 @@ pub use dispatcher::{
 };
 
 pub use guard::{allow, deny, Guard};
+
+pub use hosts::Hosts;
+
+pub use versioned::Versioned;
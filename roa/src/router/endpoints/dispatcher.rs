@@ -71,6 +71,29 @@ impl_http_functions!(trace, Method::TRACE);
 impl_http_functions!(connect, Method::CONNECT);
 
 impl<S> Dispatcher<S> {
+    /// Method to add or override endpoint on a specific method.
+    ///
+    /// ```rust
+    /// use roa::http::Method;
+    /// use roa::router::Dispatcher;
+    /// use roa::{App, Context, Result};
+    ///
+    /// async fn end(ctx: &mut Context) -> Result {
+    ///     Ok(())
+    /// }
+    ///
+    /// let dispatcher = Dispatcher::default().method(Method::GET, end);
+    /// let app = App::new().end(dispatcher);
+    /// ```
+    pub fn method(
+        mut self,
+        method: Method,
+        endpoint: impl for<'a> Endpoint<'a, S>,
+    ) -> Self {
+        self.0.insert(method, Box::new(endpoint));
+        self
+    }
+
     impl_http_methods!(get, Method::GET);
     impl_http_methods!(post, Method::POST);
     impl_http_methods!(put, Method::PUT);
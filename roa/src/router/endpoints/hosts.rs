@@ -0,0 +1,239 @@
+use crate::forward::Forward;
+use crate::http::header::HOST;
+use crate::http::StatusCode;
+use crate::router::store_param;
+use crate::{async_trait, throw, Boxed, Context, Endpoint, EndpointExt, Result, State};
+use std::collections::HashMap;
+
+/// Name of the router variable captured by wildcard hosts.
+const SUBDOMAIN: &str = "subdomain";
+
+/// An endpoint wrapper to dispatch requests by host.
+///
+/// - Exact hosts like `example.com` are matched first.
+/// - Then wildcard hosts like `*.tenant.example.com`, the longest one wins,
+///   and the subdomain is captured as router variable `subdomain`.
+/// - Then the default host, otherwise 404 NOT FOUND.
+///
+/// Hosts are matched case-insensitively, ports are ignored.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::router::{Hosts, Router, RouterParam, RouterError};
+/// use roa::{App, Context, Result};
+///
+/// async fn home(ctx: &mut Context) -> Result {
+///     ctx.resp.write("home");
+///     Ok(())
+/// }
+///
+/// async fn tenant(ctx: &mut Context) -> Result {
+///     let tenant = ctx.must_param("subdomain")?.to_string();
+///     ctx.resp.write(format!("tenant {}", tenant));
+///     Ok(())
+/// }
+///
+/// # fn main() -> std::result::Result<(), RouterError> {
+/// let hosts = Hosts::new()
+///     .host("example.com", home)
+///     .host("*.tenant.example.com", Router::new().on("/", tenant).routes("/")?)
+///     .default_host(home);
+/// let app = App::new().end(hosts);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Hosts<S> {
+    exact: HashMap<String, Boxed<S>>,
+    // suffixes of wildcard hosts, sorted by length in descending order.
+    wildcards: Vec<(String, Boxed<S>)>,
+    default: Option<Boxed<S>>,
+    forwarded: bool,
+}
+
+impl<S> Hosts<S>
+where
+    S: 'static,
+{
+    /// Construct an empty `Hosts`.
+    pub fn new() -> Self {
+        Self {
+            exact: HashMap::new(),
+            wildcards: Vec::new(),
+            default: None,
+            forwarded: false,
+        }
+    }
+
+    /// Add or override endpoint on an exact host or a wildcard host like `*.example.com`.
+    pub fn host(mut self, host: &str, endpoint: impl for<'a> Endpoint<'a, S>) -> Self {
+        let host = host.to_lowercase();
+        if host.starts_with("*.") {
+            let suffix = host[1..].to_string();
+            self.wildcards.retain(|(pattern, _)| *pattern != suffix);
+            let index = self
+                .wildcards
+                .iter()
+                .position(|(pattern, _)| pattern.len() < suffix.len())
+                .unwrap_or(self.wildcards.len());
+            self.wildcards.insert(index, (suffix, endpoint.boxed()));
+        } else {
+            self.exact.insert(host, endpoint.boxed());
+        }
+        self
+    }
+
+    /// Set endpoint of requests matching no host.
+    pub fn default_host(mut self, endpoint: impl for<'a> Endpoint<'a, S>) -> Self {
+        self.default = Some(endpoint.boxed());
+        self
+    }
+
+    /// Get host by `Forward::host`, it's used behind proxies.
+    ///
+    /// Disabled by default, the "host" header is used.
+    pub fn forwarded(mut self, enable: bool) -> Self {
+        self.forwarded = enable;
+        self
+    }
+}
+
+impl<S> Default for Hosts<S>
+where
+    S: 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Strip port and convert to lowercase.
+fn normalize_host(host: &str) -> String {
+    let host = if host.starts_with('[') {
+        // ipv6 like [::1]:8000
+        match host.find(']') {
+            Some(index) => &host[..=index],
+            None => host,
+        }
+    } else {
+        host.split(':').next().unwrap_or(host)
+    };
+    host.trim_end_matches('.').to_lowercase()
+}
+
+#[async_trait(?Send)]
+impl<'a, S> Endpoint<'a, S> for Hosts<S>
+where
+    S: State,
+{
+    #[inline]
+    async fn call(&'a self, ctx: &'a mut Context<S>) -> Result {
+        let host = if self.forwarded {
+            ctx.host()
+        } else {
+            ctx.get(HOST)
+        };
+        let host = host.or_else(|| ctx.uri().host()).map(normalize_host);
+
+        if let Some(host) = host {
+            if let Some(endpoint) = self.exact.get(&host) {
+                return endpoint.call(ctx).await;
+            }
+            for (suffix, endpoint) in self.wildcards.iter() {
+                if host.len() > suffix.len() && host.ends_with(suffix.as_str()) {
+                    let subdomain = host[..host.len() - suffix.len()].to_string();
+                    store_param(ctx, SUBDOMAIN, subdomain);
+                    return endpoint.call(ctx).await;
+                }
+            }
+        }
+
+        match self.default {
+            Some(ref endpoint) => endpoint.call(ctx).await,
+            None => throw!(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::{normalize_host, Hosts};
+    use crate::http::header::HOST;
+    use crate::http::StatusCode;
+    use crate::preload::*;
+    use crate::router::Router;
+    use crate::{App, Context};
+    use async_std::task::spawn;
+    use test_case::test_case;
+
+    #[test_case("example.com" => "example.com"; "plain")]
+    #[test_case("Example.COM:8000" => "example.com"; "port and case")]
+    #[test_case("[::1]:8000" => "[::1]"; "ipv6")]
+    #[test_case("example.com." => "example.com"; "trailing dot")]
+    fn normalize(host: &str) -> String {
+        normalize_host(host)
+    }
+
+    async fn home(ctx: &mut Context) -> crate::Result {
+        ctx.resp.write("home");
+        Ok(())
+    }
+
+    async fn tenant(ctx: &mut Context) -> crate::Result {
+        let tenant = ctx.must_param("subdomain")?.to_string();
+        ctx.resp.write(format!("tenant {}", tenant));
+        Ok(())
+    }
+
+    async fn admin(ctx: &mut Context) -> crate::Result {
+        let tenant = ctx.must_param("subdomain")?.to_string();
+        ctx.resp.write(format!("admin {}", tenant));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hosts() -> Result<(), Box<dyn std::error::Error>> {
+        let hosts = Hosts::new()
+            .host("example.com", home)
+            .host("*.example.com", tenant)
+            .host("*.admin.example.com", admin);
+        let (addr, server) = App::new().end(hosts).run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let get = |host: &'static str| {
+            client
+                .get(&format!("http://{}", addr))
+                .header(HOST, host)
+                .send()
+        };
+        assert_eq!("home", get("Example.com:80").await?.text().await?);
+        assert_eq!("tenant foo", get("foo.example.com").await?.text().await?);
+        assert_eq!(
+            "admin foo.bar",
+            get("foo.bar.admin.example.com").await?.text().await?
+        );
+        assert_eq!(StatusCode::NOT_FOUND, get("github.com").await?.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn forwarded_and_default() -> Result<(), Box<dyn std::error::Error>> {
+        let tenant_router = Router::new().on("/user", tenant);
+        let hosts = Hosts::new()
+            .host("*.example.com", tenant_router.routes("/")?)
+            .default_host(home)
+            .forwarded(true);
+        let (addr, server) = App::new().end(hosts).run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}/user", addr))
+            .header("x-forwarded-host", "foo.example.com")
+            .send()
+            .await?;
+        assert_eq!("tenant foo", resp.text().await?);
+        let resp = client.get(&format!("http://{}/user", addr)).send().await?;
+        assert_eq!("home", resp.text().await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,344 @@
+use crate::http::header::{HeaderName, HeaderValue, ACCEPT, VARY};
+use crate::http::StatusCode;
+use crate::router::RouterParam;
+use crate::{async_trait, throw, Boxed, Context, Endpoint, EndpointExt, Result};
+use headers::{Date, Header};
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Name of the router variable of versions.
+const VERSION: &str = "version";
+
+/// Name of the request header of versions.
+const API_VERSION: &str = "api-version";
+
+/// Name of the response header listing supported versions.
+const API_SUPPORTED_VERSIONS: &str = "api-supported-versions";
+
+/// Name of the response header of deprecated versions.
+const DEPRECATION: &str = "deprecation";
+
+/// Name of the response header of sunset date.
+const SUNSET: &str = "sunset";
+
+/// An endpoint wrapper to dispatch requests by api version.
+///
+/// The requested version is taken from, in order:
+/// - router variable `version`, like `/:version/user`;
+/// - header `Api-Version`;
+/// - vendor media type in `Accept`, like `application/vnd.app.v2+json`.
+///
+/// Versions are matched ignoring the leading `v`, so `2` and `v2` are the same version.
+/// Requests without version are passed to the default version,
+/// requests with an unsupported version get a 404 NOT FOUND.
+///
+/// Supported versions are listed in response header `Api-Supported-Versions`,
+/// and responses of deprecated versions have headers `Deprecation` and `Sunset`.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::router::{Router, RouterError, Versioned};
+/// use roa::{App, Context, Result};
+/// use std::time::{Duration, SystemTime};
+///
+/// async fn user_v1(ctx: &mut Context) -> Result {
+///     ctx.resp.write("user v1");
+///     Ok(())
+/// }
+///
+/// async fn user_v2(ctx: &mut Context) -> Result {
+///     ctx.resp.write("user v2");
+///     Ok(())
+/// }
+///
+/// # fn main() -> std::result::Result<(), RouterError> {
+/// let sunset = SystemTime::now() + Duration::from_secs(30 * 24 * 3600);
+/// let user = Versioned::new()
+///     .version("v1", user_v1)
+///     .version("v2", user_v2)
+///     .default_version("v2")
+///     .deprecate("v1", sunset)
+///     .vendor("app");
+/// let router = Router::new().on("/user", user);
+/// let app = App::new().end(router.routes("/")?);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Versioned<S> {
+    // versions in order of registration.
+    versions: Vec<(String, Boxed<S>)>,
+    // sunset dates of deprecated versions.
+    deprecations: HashMap<String, Option<SystemTime>>,
+    default: Option<String>,
+    vendor: Option<String>,
+}
+
+/// Normalize a version, `V2`, `v2` and `2` are the same version.
+fn normalize_version(version: &str) -> String {
+    version
+        .trim()
+        .trim_start_matches(&['v', 'V'][..])
+        .to_string()
+}
+
+impl<S> Versioned<S>
+where
+    S: 'static,
+{
+    /// Construct an empty `Versioned`.
+    pub fn new() -> Self {
+        Self {
+            versions: Vec::new(),
+            deprecations: HashMap::new(),
+            default: None,
+            vendor: None,
+        }
+    }
+
+    /// Add or override endpoint on a version.
+    pub fn version(
+        mut self,
+        version: &str,
+        endpoint: impl for<'a> Endpoint<'a, S>,
+    ) -> Self {
+        let key = normalize_version(version);
+        let endpoint = endpoint.boxed();
+        match self
+            .versions
+            .iter_mut()
+            .find(|(name, _)| normalize_version(name) == key)
+        {
+            Some(pair) => *pair = (version.to_string(), endpoint),
+            None => self.versions.push((version.to_string(), endpoint)),
+        }
+        self
+    }
+
+    /// Set the version of requests without version.
+    pub fn default_version(mut self, version: &str) -> Self {
+        self.default = Some(normalize_version(version));
+        self
+    }
+
+    /// Mark a version as deprecated, with an optional sunset date.
+    pub fn deprecate(
+        mut self,
+        version: &str,
+        sunset: impl Into<Option<SystemTime>>,
+    ) -> Self {
+        self.deprecations
+            .insert(normalize_version(version), sunset.into());
+        self
+    }
+
+    /// Only match vendor media types of a vendor, like `application/vnd.{vendor}.v2+json`.
+    ///
+    /// Vendor media types of any vendor are matched by default.
+    pub fn vendor(mut self, vendor: &str) -> Self {
+        self.vendor = Some(vendor.to_lowercase());
+        self
+    }
+
+    /// Get all supported versions in order of registration.
+    pub fn versions(&self) -> Vec<&str> {
+        self.versions
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Get the requested version.
+    fn requested(&self, ctx: &Context<S>) -> Option<String> {
+        if let Some(version) = ctx.param(VERSION) {
+            return Some(normalize_version(&version));
+        }
+        if let Some(version) = ctx.get(API_VERSION) {
+            return Some(normalize_version(version));
+        }
+        let accept = ctx.get(ACCEPT)?.to_lowercase();
+        accept
+            .split(',')
+            .filter_map(|media_type| self.vendor_version(media_type))
+            .next()
+    }
+
+    /// Get version in a vendor media type like `application/vnd.app.v2+json`.
+    fn vendor_version(&self, media_type: &str) -> Option<String> {
+        let essence = media_type.split(';').next()?.trim();
+        let subtype = essence.trim_start_matches("application/vnd.");
+        if subtype.len() == essence.len() {
+            return None;
+        }
+        let subtype = subtype.split('+').next()?;
+        let index = subtype.rfind('.')?;
+        let (vendor, version) = (&subtype[..index], &subtype[index + 1..]);
+        match self.vendor {
+            Some(ref expected) if expected != vendor => None,
+            _ => Some(normalize_version(version)),
+        }
+    }
+
+    /// Set headers of versions.
+    fn set_headers(&self, ctx: &mut Context<S>, version: &str) -> Result {
+        let headers = &mut ctx.resp.headers;
+        headers.append(VARY, HeaderValue::from_name(ACCEPT));
+        headers.append(VARY, HeaderValue::from_static(API_VERSION));
+        headers.insert(
+            HeaderName::from_static(API_SUPPORTED_VERSIONS),
+            self.versions().join(", ").parse()?,
+        );
+        if let Some(sunset) = self.deprecations.get(version) {
+            headers.insert(
+                HeaderName::from_static(DEPRECATION),
+                HeaderValue::from_static("true"),
+            );
+            if let Some(sunset) = sunset {
+                let mut values = Vec::new();
+                Date::from(*sunset).encode(&mut values);
+                if let Some(value) = values.pop() {
+                    headers.insert(HeaderName::from_static(SUNSET), value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S> Default for Versioned<S>
+where
+    S: 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, S> Endpoint<'a, S> for Versioned<S>
+where
+    S: 'static,
+{
+    #[inline]
+    async fn call(&'a self, ctx: &'a mut Context<S>) -> Result {
+        let version = match self.requested(ctx).or_else(|| self.default.clone()) {
+            Some(version) => version,
+            None => throw!(StatusCode::BAD_REQUEST, "api version is required"),
+        };
+        let endpoint = self
+            .versions
+            .iter()
+            .find(|(name, _)| normalize_version(name) == version);
+        match endpoint {
+            Some((_, endpoint)) => {
+                self.set_headers(ctx, &version)?;
+                endpoint.call(ctx).await
+            }
+            None => throw!(
+                StatusCode::NOT_FOUND,
+                format!("api version `{}` is not supported", version)
+            ),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::Versioned;
+    use crate::http::header::ACCEPT;
+    use crate::http::StatusCode;
+    use crate::preload::*;
+    use crate::router::Router;
+    use crate::{App, Context};
+    use async_std::task::spawn;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    async fn v1(ctx: &mut Context) -> crate::Result {
+        ctx.resp.write("v1");
+        Ok(())
+    }
+
+    async fn v2(ctx: &mut Context) -> crate::Result {
+        ctx.resp.write("v2");
+        Ok(())
+    }
+
+    fn versioned() -> Versioned<()> {
+        Versioned::new()
+            .version("v1", v1)
+            .version("v2", v2)
+            .deprecate("v1", UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+    }
+
+    #[test]
+    fn vendor_version() {
+        let versioned = versioned();
+        assert_eq!(
+            Some("2".to_string()),
+            versioned.vendor_version("application/vnd.app.v2+json; q=0.9")
+        );
+        assert_eq!(None, versioned.vendor_version("application/json"));
+        let versioned = versioned.vendor("github");
+        assert_eq!(
+            None,
+            versioned.vendor_version("application/vnd.app.v2+json")
+        );
+        assert_eq!(
+            Some("3".to_string()),
+            versioned.vendor_version("application/vnd.github.v3+json")
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_by_version() -> Result<(), Box<dyn std::error::Error>> {
+        let router = Router::new()
+            .on("/:version/user", versioned())
+            .on("/user", versioned().default_version("v2"));
+        let (addr, server) = App::new().end(router.routes("/")?).run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        let resp = client
+            .get(&format!("http://{}/v1/user", addr))
+            .send()
+            .await?;
+        assert_eq!("true", resp.headers()["deprecation"]);
+        assert_eq!("Sun, 13 Sep 2020 12:26:40 GMT", resp.headers()["sunset"]);
+        assert_eq!("v1, v2", resp.headers()["api-supported-versions"]);
+        assert_eq!("v1", resp.text().await?);
+
+        let resp = client
+            .get(&format!("http://{}/v3/user", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+
+        let resp = client.get(&format!("http://{}/user", addr)).send().await?;
+        assert!(resp.headers().get("deprecation").is_none());
+        assert_eq!("v2", resp.text().await?);
+
+        let resp = client
+            .get(&format!("http://{}/user", addr))
+            .header("api-version", "1")
+            .send()
+            .await?;
+        assert_eq!("v1", resp.text().await?);
+
+        let resp = client
+            .get(&format!("http://{}/user", addr))
+            .header(ACCEPT, "text/html, application/vnd.app.v1+json")
+            .send()
+            .await?;
+        assert_eq!("v1", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn version_required() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new().end(versioned()).run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use roa_core::http;
+use roa_path::PathError;
 use std::fmt::{self, Display, Formatter};
 
 /// Error occurring in building route table.
@@ -9,6 +10,16 @@ pub enum RouterError {
 
     /// Variables, methods or paths conflict.
     Conflict(Conflict),
+
+    /// Constraint of a variable is invalid, like an invalid regex or an unknown type.
+    InvalidConstraint {
+        path: String,
+        var_name: String,
+        reason: String,
+    },
+
+    /// Prefixes of mounts and fallbacks are matched literally, variables are not allowed.
+    DynamicPrefix(String),
 }
 
 /// Router conflict.
@@ -16,6 +27,8 @@ pub enum RouterError {
 pub enum Conflict {
     Path(String),
     Method(String, http::Method),
+    Fallback(String),
+    Mount(String),
     Variable {
         paths: (String, String),
         var_name: String,
@@ -26,6 +39,12 @@ impl Display for Conflict {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
             Conflict::Path(path) => f.write_str(&format!("conflict path: `{}`", path)),
+            Conflict::Fallback(prefix) => {
+                f.write_str(&format!("conflict fallback: `{}`", prefix))
+            }
+            Conflict::Mount(prefix) => {
+                f.write_str(&format!("conflict mount: `{}`", prefix))
+            }
             Conflict::Method(path, method) => f.write_str(&format!(
                 "conflict method: `{}` on `{}` is already set",
                 method, path
@@ -47,6 +66,18 @@ impl Display for RouterError {
             RouterError::MissingVariable(path) => {
                 f.write_str(&format!("missing variable on path {}", path))
             }
+            RouterError::InvalidConstraint {
+                path,
+                var_name,
+                reason,
+            } => f.write_str(&format!(
+                "invalid constraint of variable `{}` on path {}: {}",
+                var_name, path, reason
+            )),
+            RouterError::DynamicPrefix(prefix) => f.write_str(&format!(
+                "dynamic prefix `{}` is not allowed in mounts or fallbacks",
+                prefix
+            )),
         }
     }
 }
@@ -57,6 +88,29 @@ impl From<Conflict> for RouterError {
     }
 }
 
+impl From<PathError> for RouterError {
+    fn from(err: PathError) -> Self {
+        match err {
+            PathError::MissingVariable(path) => RouterError::MissingVariable(path),
+            PathError::ConflictVariable { path, var_name } => {
+                RouterError::Conflict(Conflict::Variable {
+                    paths: (path.clone(), path),
+                    var_name,
+                })
+            }
+            PathError::InvalidConstraint {
+                path,
+                var_name,
+                reason,
+            } => RouterError::InvalidConstraint {
+                path,
+                var_name,
+                reason,
+            },
+        }
+    }
+}
+
 impl std::error::Error for Conflict {}
 impl std::error::Error for RouterError {}
 
@@ -71,6 +125,14 @@ mod tests {
             "conflict path: `/`",
             Conflict::Path("/".to_string()).to_string()
         );
+        assert_eq!(
+            "conflict fallback: `/api/`",
+            Conflict::Fallback("/api/".to_string()).to_string()
+        );
+        assert_eq!(
+            "conflict mount: `/v2/`",
+            Conflict::Mount("/v2/".to_string()).to_string()
+        );
         assert_eq!(
             "conflict method: `GET` on `/` is already set",
             Conflict::Method("/".to_string(), http::Method::GET).to_string()
@@ -95,5 +157,18 @@ mod tests {
             "missing variable on path /:",
             RouterError::MissingVariable("/:".to_string()).to_string()
         );
+        assert_eq!(
+            "invalid constraint of variable `id` on path /:id<f64>: unknown type `f64`",
+            RouterError::InvalidConstraint {
+                path: "/:id<f64>".to_string(),
+                var_name: "id".to_string(),
+                reason: "unknown type `f64`".to_string(),
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "dynamic prefix `/:id/` is not allowed in mounts or fallbacks",
+            RouterError::DynamicPrefix("/:id/".to_string()).to_string()
+        );
     }
 }
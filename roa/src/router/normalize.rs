@@ -0,0 +1,97 @@
+use crate::http::StatusCode;
+
+/// Policy of trailing slashes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TrailingSlash {
+    /// `/path` and `/path/` match the same routes, it's the default policy.
+    Lenient,
+
+    /// `/path` and `/path/` are different routes.
+    Strict,
+
+    /// Like `Strict`, but redirect to the registered form with 301 MOVED PERMANENTLY
+    /// if only the other form matches.
+    MovedPermanently,
+
+    /// Like `Strict`, but redirect to the registered form with 308 PERMANENT REDIRECT
+    /// if only the other form matches.
+    PermanentRedirect,
+}
+
+/// Normalization policy of `RouteTable`.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::router::{Normalization, Router, RouterError, TrailingSlash};
+/// use roa::{App, Context, Result};
+///
+/// async fn end(_ctx: &mut Context) -> Result {
+///     Ok(())
+/// }
+///
+/// # fn main() -> std::result::Result<(), RouterError> {
+/// let normalization = Normalization::new()
+///     .trailing_slash(TrailingSlash::PermanentRedirect)
+///     .case_insensitive(true)
+///     .raw_segments(true);
+/// let router = Router::new().on("/user/", end);
+/// let app = App::new().end(router.routes_with("/", normalization)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Normalization {
+    pub(crate) trailing_slash: TrailingSlash,
+    pub(crate) case_insensitive: bool,
+    pub(crate) raw_segments: bool,
+}
+
+impl Normalization {
+    /// Construct default policy: lenient trailing slashes,
+    /// case-sensitive and matching on decoded path.
+    pub fn new() -> Self {
+        Self {
+            trailing_slash: TrailingSlash::Lenient,
+            case_insensitive: false,
+            raw_segments: false,
+        }
+    }
+
+    /// Set policy of trailing slashes.
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Match static segments case-insensitively,
+    /// values of variables keep their original case.
+    pub fn case_insensitive(mut self, enable: bool) -> Self {
+        self.case_insensitive = enable;
+        self
+    }
+
+    /// Keep encoded `/` and `%` (`%2F` and `%25`) while matching,
+    /// and decode values of variables after matching.
+    ///
+    /// So `/file/a%2Fb` matches `/file/:name` with `name` = "a/b".
+    pub fn raw_segments(mut self, enable: bool) -> Self {
+        self.raw_segments = enable;
+        self
+    }
+
+    /// Status to redirect requests with the other form of trailing slash.
+    pub(crate) fn redirect_status(&self) -> Option<StatusCode> {
+        match self.trailing_slash {
+            TrailingSlash::MovedPermanently => Some(StatusCode::MOVED_PERMANENTLY),
+            TrailingSlash::PermanentRedirect => Some(StatusCode::PERMANENT_REDIRECT),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self::new()
+    }
+}
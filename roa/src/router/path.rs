@@ -1,41 +1,53 @@
-use super::{Conflict, RouterError};
-use regex::{escape, Captures, Regex};
+use super::RouterError;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+pub use roa_path::standardize_path;
+use roa_path::{must_build, path_to_regexp};
 use std::collections::HashSet;
 use std::convert::AsRef;
 use std::str::FromStr;
+use std::string::FromUtf8Error;
 
-/// Match pattern *{variable}
-const WILDCARD: &str = r"\*\{(?P<var>\w*)\}";
-
-/// Match pattern /:variable/
-const VARIABLE: &str = r"/:(?P<var>\w*)/";
-
-/// {/path path/ /path/} => /path/
-pub fn standardize_path(raw_path: &str) -> String {
-    format!("/{}/", raw_path.trim_matches('/'))
-}
-
-/// Join multiple segments.
+/// Join multiple segments, the trailing slash of the last segment is preserved.
 pub fn join_path<'a>(paths: impl 'a + AsRef<[&'a str]>) -> String {
-    paths
-        .as_ref()
+    let paths = paths.as_ref();
+    let mut joined = paths
         .iter()
         .map(|path| path.trim_matches('/'))
         .filter(|path| !path.is_empty())
         .collect::<Vec<&str>>()
-        .join("/")
+        .join("/");
+    if let Some(last) = paths.last() {
+        if has_trailing_slash(last) && !joined.is_empty() {
+            joined.push('/');
+        }
+    }
+    joined
+}
+
+/// Whether a path ends with a slash, the root path is not included.
+pub fn has_trailing_slash(path: &str) -> bool {
+    path.ends_with('/') && !path.trim_matches('/').is_empty()
 }
 
-/// Build pattern.
-fn must_build(pattern: &str) -> Regex {
-    Regex::new(pattern).unwrap_or_else(|err| {
-        panic!(
-            r#"{}
-                regex pattern {} is invalid, this is a bug of roa::router::path.
-                please report it to https://github.com/Hexilee/roa"#,
-            err, pattern
-        )
-    })
+/// Percent-decode a path, except for `%2F` and `%25`.
+pub fn decode_segments(path: &str) -> Result<String, FromUtf8Error> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut last = 0;
+    for (index, _) in path.match_indices('%') {
+        if index < last {
+            continue;
+        }
+        if let Some(hex) = path.get(index + 1..index + 3) {
+            if hex.eq_ignore_ascii_case("2f") || hex == "25" {
+                decoded.extend(percent_decode_str(&path[last..index]));
+                decoded.extend_from_slice(&path.as_bytes()[index..index + 3]);
+                last = index + 3;
+            }
+        }
+    }
+    decoded.extend(percent_decode_str(&path[last..]));
+    String::from_utf8(decoded)
 }
 
 /// Parsed path.
@@ -53,6 +65,16 @@ pub struct RegexPath {
     pub re: Regex,
 }
 
+impl RegexPath {
+    /// Rebuild regex to match case-insensitively.
+    pub fn case_insensitive(self) -> Self {
+        Self {
+            re: must_build(&format!(r"(?i){}", self.re.as_str())),
+            ..self
+        }
+    }
+}
+
 impl FromStr for Path {
     type Err = RouterError;
     fn from_str(raw_path: &str) -> Result<Self, Self::Err> {
@@ -68,127 +90,11 @@ impl FromStr for Path {
     }
 }
 
-fn path_to_regexp(path: &str) -> Result<Option<(String, HashSet<String>)>, RouterError> {
-    let mut pattern = escape(path);
-    let mut vars = HashSet::new();
-    let wildcard_re = must_build(WILDCARD);
-    let variable_re = must_build(VARIABLE);
-    let wildcards: Vec<Captures> = wildcard_re.captures_iter(path).collect();
-    let variable_template = path.replace('/', "//"); // to match continuous variables like /:year/:month/:day/
-    let variables: Vec<Captures> =
-        variable_re.captures_iter(&variable_template).collect();
-    if wildcards.is_empty() && variables.is_empty() {
-        Ok(None)
-    } else {
-        // detect variable conflicts.
-        let try_add_variable = |set: &mut HashSet<String>, variable: String| {
-            if set.insert(variable.clone()) {
-                Ok(())
-            } else {
-                Err(Conflict::Variable {
-                    paths: (path.to_string(), path.to_string()),
-                    var_name: variable,
-                })
-            }
-        };
-
-        // match wildcard patterns
-        for cap in wildcards {
-            let variable = &cap["var"];
-            if variable == r"" {
-                return Err(RouterError::MissingVariable(path.to_string()));
-            }
-            let var = escape(variable);
-            pattern = pattern.replace(
-                &escape(&format!(r"*{{{}}}", variable)),
-                &format!(r"(?P<{}>\S+)", &var),
-            );
-            try_add_variable(&mut vars, var)?;
-        }
-
-        // match segment variable patterns
-        for cap in variables {
-            let variable = &cap["var"];
-            if variable == "" {
-                return Err(RouterError::MissingVariable(path.to_string()));
-            }
-            let var = escape(variable);
-            pattern = pattern.replace(
-                &escape(&format!(r":{}", variable)),
-                &format!(r"(?P<{}>[^\s/]+)", &var),
-            );
-            try_add_variable(&mut vars, var)?;
-        }
-        Ok(Some((pattern, vars)))
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use super::Path;
-    use super::{must_build, path_to_regexp, VARIABLE, WILDCARD};
+    use super::{decode_segments, join_path, Path, RouterError};
     use test_case::test_case;
 
-    #[test_case("/:id/"; "pure dynamic")]
-    #[test_case("/user/:id/"; "static prefix")]
-    #[test_case("/user/:id/name"; "static prefix and suffix")]
-    fn var_regex_match(path: &str) {
-        let re = must_build(VARIABLE);
-        let cap = re.captures(path);
-        assert!(cap.is_some());
-        assert_eq!("id", &cap.unwrap()["var"]);
-    }
-
-    #[test_case("/-:id/"; "invalid prefix")]
-    #[test_case("/:i-d/"; "invalid variable name")]
-    #[test_case("/:id-/"; "invalid suffix")]
-    fn var_regex_mismatch(path: &str) {
-        let re = must_build(VARIABLE);
-        let cap = re.captures(path);
-        assert!(cap.is_none());
-    }
-
-    #[test_case("*{id}"; "pure dynamic")]
-    #[test_case("user-*{id}"; "static prefix")]
-    #[test_case("user-*{id}-name"; "static prefix and suffix")]
-    fn wildcard_regex_match(path: &str) {
-        let re = must_build(WILDCARD);
-        let cap = re.captures(path);
-        assert!(cap.is_some());
-        assert_eq!("id", &cap.unwrap()["var"]);
-    }
-
-    #[test_case("*"; "no variable")]
-    #[test_case("*{-id}"; "invalid variable name")]
-    fn wildcard_regex_mismatch(path: &str) {
-        let re = must_build(WILDCARD);
-        let cap = re.captures(path);
-        assert!(cap.is_none());
-    }
-
-    #[test_case(r"/:id/" => r"/(?P<id>[^\s/]+)/"; "single variable")]
-    #[test_case(r"/:year/:month/:day/" => r"/(?P<year>[^\s/]+)/(?P<month>[^\s/]+)/(?P<day>[^\s/]+)/"; "multiple variable")]
-    #[test_case(r"*{id}" => r"(?P<id>\S+)"; "single wildcard")]
-    #[test_case(r"*{year}_*{month}_*{day}" => r"(?P<year>\S+)_(?P<month>\S+)_(?P<day>\S+)"; "multiple wildcard")]
-    fn path_to_regexp_dynamic_pattern(path: &str) -> String {
-        path_to_regexp(path).unwrap().unwrap().0
-    }
-
-    #[test_case(r"/id/")]
-    #[test_case(r"/user/post/")]
-    fn path_to_regexp_static(path: &str) {
-        assert!(path_to_regexp(path).unwrap().is_none())
-    }
-
-    #[test_case(r"/:/"; "missing variable name")]
-    #[test_case(r"*{}"; "wildcard missing variable name")]
-    #[test_case(r"/:id/:id/"; "conflict variable")]
-    #[test_case(r"*{id}-*{id}"; "wildcard conflict variable")]
-    #[test_case(r"/:id/*{id}"; "mix conflict variable")]
-    fn path_to_regexp_err(path: &str) {
-        assert!(path_to_regexp(path).is_err())
-    }
-
     fn path_match(pattern: &str, path: &str) {
         let pattern: Path = pattern.parse().unwrap();
         match pattern {
@@ -238,9 +144,57 @@ mod tests {
         path_not_match(r"/srv/:path/", path)
     }
 
-    #[should_panic]
+    #[test_case(r"/user/1/")]
+    #[test_case(r"/user/65535/")]
+    fn constrained_variable_path_match(path: &str) {
+        path_match(r"/user/:id(\d+)", path);
+        path_match(r"/user/:id<u64>", path);
+    }
+
+    #[test_case(r"/user/abc/")]
+    #[test_case(r"/user/-1/")]
+    #[test_case(r"/user/1a/")]
+    fn constrained_variable_path_not_match(path: &str) {
+        path_not_match(r"/user/:id(\d+)", path);
+        path_not_match(r"/user/:id<u64>", path);
+    }
+
+    #[test_case(r"/user/67e55044-10b1-426f-9247-bb680e5fe0c8/")]
+    #[test_case(r"/user/")]
+    fn optional_uuid_path_match(path: &str) {
+        path_match(r"/user/:id<uuid>?", path)
+    }
+
+    #[test_case(r"/files/")]
+    #[test_case(r"/files/app/index.html/")]
+    fn optional_wildcard_path_match(path: &str) {
+        path_match(r"/files/*{path}?", path)
+    }
+
     #[test]
-    fn must_build_fails() {
-        must_build(r"{");
+    fn unknown_type() {
+        match r"/:id<f64>".parse::<Path>() {
+            Err(RouterError::InvalidConstraint { var_name, .. }) => {
+                assert_eq!("id", var_name)
+            }
+            _ => panic!("`/:id<f64>` should be invalid"),
+        }
+    }
+
+    #[test_case(&["/api", "/user"] => "api/user"; "no trailing slash")]
+    #[test_case(&["/api", "/user/"] => "api/user/"; "trailing slash")]
+    #[test_case(&["/api/", "/"] => "api"; "root")]
+    #[test_case(&["/", "/"] => ""; "empty")]
+    fn join_path_trailing_slash(paths: &[&str]) -> String {
+        join_path(paths)
+    }
+
+    #[test_case("/a%20b/" => "/a b/"; "decode space")]
+    #[test_case("/a%2Fb/" => "/a%2Fb/"; "keep slash")]
+    #[test_case("/a%2fb/" => "/a%2fb/"; "keep lowercase slash")]
+    #[test_case("/a%25%41/" => "/a%25A/"; "keep percent")]
+    #[test_case("/%E8%B7%AF%2F%E7%94%B1/" => "/路%2F由/"; "utf8")]
+    fn decode_raw_segments(path: &str) -> String {
+        decode_segments(path).unwrap()
     }
 }
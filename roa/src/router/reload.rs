@@ -0,0 +1,258 @@
+use super::{path::standardize_path, RouteTable};
+use crate::{async_trait, Context, Endpoint, Result};
+use arc_swap::ArcSwap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// An endpoint wrapper of `RouteTable` which can be replaced at runtime.
+///
+/// `ReloadableRouter` is cheap to clone, clones share the same table,
+/// so you can reload it in a background task or disable routes in an admin endpoint.
+/// In-flight requests keep using the table they started with.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::router::{get, ReloadableRouter, Router, RouterError};
+/// use roa::{App, Context, Result};
+///
+/// async fn v1(ctx: &mut Context) -> Result {
+///     ctx.resp.write("v1");
+///     Ok(())
+/// }
+///
+/// async fn v2(ctx: &mut Context) -> Result {
+///     ctx.resp.write("v2");
+///     Ok(())
+/// }
+///
+/// # fn main() -> std::result::Result<(), RouterError> {
+/// let router = ReloadableRouter::new(Router::new().on("/version", get(v1)).routes("/")?);
+/// let app = App::new().end(router.clone());
+///
+/// // in a background task or an admin endpoint
+/// router.reload(Router::new().on("/version", get(v2)).routes("/")?);
+/// assert!(router.disable("/version"));
+/// router.enable("/version");
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReloadableRouter<S> {
+    table: Arc<ArcSwap<RouteTable<S>>>,
+    // standardized paths of disabled routes.
+    disabled: Arc<ArcSwap<HashSet<String>>>,
+}
+
+impl<S> ReloadableRouter<S>
+where
+    S: 'static,
+{
+    /// Construct a reloadable router by a route table.
+    pub fn new(table: RouteTable<S>) -> Self {
+        Self {
+            table: Arc::new(ArcSwap::from_pointee(table)),
+            disabled: Arc::new(ArcSwap::from_pointee(HashSet::new())),
+        }
+    }
+
+    /// Replace the route table, disabled routes are kept.
+    pub fn reload(&self, table: RouteTable<S>) {
+        self.table.store(Arc::new(table));
+    }
+
+    /// Disable a route by the path it was registered on, including the prefix,
+    /// like `/api/user/:id`.
+    ///
+    /// Requests will be handled as if the route did not exist,
+    /// routes of mounted endpoints are not affected.
+    ///
+    /// Return false and disable nothing if no route is registered on the path.
+    pub fn disable(&self, path: &str) -> bool {
+        let path = standardize_path(path);
+        if !self.table.load().contains(&path) {
+            return false;
+        }
+        self.disabled.rcu(|disabled| {
+            let mut disabled = HashSet::clone(disabled);
+            disabled.insert(path.clone());
+            disabled
+        });
+        true
+    }
+
+    /// Enable a disabled route.
+    pub fn enable(&self, path: &str) {
+        let path = standardize_path(path);
+        self.disabled.rcu(|disabled| {
+            let mut disabled = HashSet::clone(disabled);
+            disabled.remove(&path);
+            disabled
+        });
+    }
+
+    /// Whether a route is disabled.
+    pub fn is_disabled(&self, path: &str) -> bool {
+        self.disabled.load().contains(&standardize_path(path))
+    }
+}
+
+impl<S> Clone for ReloadableRouter<S> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            disabled: self.disabled.clone(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, S> Endpoint<'a, S> for ReloadableRouter<S>
+where
+    S: 'static,
+{
+    #[inline]
+    async fn call(&'a self, ctx: &'a mut Context<S>) -> Result {
+        let table = self.table.load_full();
+        let disabled = self.disabled.load_full();
+        table.call_with(ctx, &disabled).await
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::ReloadableRouter;
+    use crate::http::StatusCode;
+    use crate::router::{get, Router};
+    use crate::tcp::Listener;
+    use crate::{App, Context, Status};
+    use async_std::task::{sleep, spawn};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    static SLOW_STARTED: AtomicBool = AtomicBool::new(false);
+
+    async fn v1(ctx: &mut Context) -> Result<(), Status> {
+        ctx.resp.write("v1");
+        Ok(())
+    }
+
+    async fn v2(ctx: &mut Context) -> Result<(), Status> {
+        ctx.resp.write("v2");
+        Ok(())
+    }
+
+    async fn slow(ctx: &mut Context) -> Result<(), Status> {
+        SLOW_STARTED.store(true, Ordering::SeqCst);
+        sleep(Duration::from_millis(300)).await;
+        ctx.resp.write("slow v1");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reload() -> Result<(), Box<dyn std::error::Error>> {
+        let router = ReloadableRouter::new(
+            Router::new()
+                .on("/version", get(v1))
+                .on("/slow", get(slow))
+                .routes("/")?,
+        );
+        let (addr, server) = App::new().end(router.clone()).run()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/version", addr)).await?;
+        assert_eq!("v1", resp.text().await?);
+
+        let in_flight = tokio::spawn(async move {
+            let resp = reqwest::get(&format!("http://{}/slow", addr)).await?;
+            resp.text().await
+        });
+        while !SLOW_STARTED.load(Ordering::SeqCst) {
+            sleep(Duration::from_millis(10)).await;
+        }
+        router.reload(Router::new().on("/version", get(v2)).routes("/")?);
+        assert_eq!("slow v1", in_flight.await??);
+
+        let resp = reqwest::get(&format!("http://{}/version", addr)).await?;
+        assert_eq!("v2", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/slow", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disable_and_enable() -> Result<(), Box<dyn std::error::Error>> {
+        async fn user(ctx: &mut Context) -> Result<(), Status> {
+            ctx.resp.write("user");
+            Ok(())
+        }
+        async fn fallback(ctx: &mut Context) -> Result<(), Status> {
+            ctx.resp.write("fallback");
+            Ok(())
+        }
+        let router = ReloadableRouter::new(
+            Router::new()
+                .on("/version", get(v1))
+                .on("/user/:id", get(user))
+                .fallback(fallback)
+                .routes("/api")?,
+        );
+        let (addr, server) = App::new().end(router.clone()).run()?;
+        spawn(server);
+
+        assert!(router.disable("/api/version"));
+        assert!(router.disable("api/user/:id/"));
+        assert!(!router.disable("/api/post"));
+        assert!(router.is_disabled("/api/version"));
+        assert!(!router.is_disabled("/api/post"));
+        let resp = reqwest::get(&format!("http://{}/api/version", addr)).await?;
+        assert_eq!("fallback", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/api/user/0", addr)).await?;
+        assert_eq!("fallback", resp.text().await?);
+
+        router.enable("/api/user/:id");
+        assert!(!router.is_disabled("/api/user/:id"));
+        let resp = reqwest::get(&format!("http://{}/api/version", addr)).await?;
+        assert_eq!("fallback", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/api/user/0", addr)).await?;
+        assert_eq!("user", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disable_with_mount() -> Result<(), Box<dyn std::error::Error>> {
+        let v2 = Router::new().on("/version", get(v2)).routes("/")?;
+        let router = ReloadableRouter::new(
+            Router::new()
+                .on("/version", get(v1))
+                .mount("/v2", v2)
+                .routes("/")?,
+        );
+        let (addr, server) = App::new().end(router.clone()).run()?;
+        spawn(server);
+
+        assert!(router.disable("/version"));
+        let resp = reqwest::get(&format!("http://{}/version", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        let resp = reqwest::get(&format!("http://{}/v2/version", addr)).await?;
+        assert_eq!("v2", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disable_with_nested() -> Result<(), Box<dyn std::error::Error>> {
+        let inner =
+            ReloadableRouter::new(Router::new().on("/version", get(v2)).routes("/")?);
+        let router = ReloadableRouter::new(
+            Router::new()
+                .on("/version", get(v1))
+                .fallback(inner)
+                .routes("/")?,
+        );
+        let (addr, server) = App::new().end(router.clone()).run()?;
+        spawn(server);
+
+        assert!(router.disable("/version"));
+        let resp = reqwest::get(&format!("http://{}/version", addr)).await?;
+        assert_eq!("v2", resp.text().await?);
+        Ok(())
+    }
+}
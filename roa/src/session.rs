@@ -0,0 +1,672 @@
+//! This module provides a middleware `SessionManager`, a context extension `SessionGetter`
+//! and session stores.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::preload::*;
+//! use roa::session::{MemoryStore, SessionManager};
+//! use roa::{App, Context};
+//!
+//! async fn login(ctx: &mut Context) -> roa::Result {
+//!     let user: Option<String> = ctx.session()?.get("user")?;
+//!     match user {
+//!         Some(user) => {
+//!             ctx.write(format!("Hello, {}!", user));
+//!         }
+//!         None => {
+//!             let session = ctx.session()?;
+//!             session.insert("user", "Hexilee")?;
+//!             // rotate the session id after login.
+//!             session.renew();
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let app = App::new()
+//!     .gate(SessionManager::new(MemoryStore::new()))
+//!     .end(login);
+//! let (addr, server) = app.run()?;
+//! // server.await
+//! Ok(())
+//! # }
+//! ```
+
+pub use crate::cookie::Key;
+pub use cookie::SameSite;
+
+use crate::cookie::{parse_cookies, removal, Cookie, CookieGetter, CookieSetter};
+use crate::http::StatusCode;
+use crate::{async_trait, Context, Middleware, Next, Result, Status};
+use cookie::CookieJar;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A private scope.
+struct SessionScope;
+
+/// Key of the session in Context::storage.
+const SESSION: &str = "session";
+
+/// Default name of the session cookie.
+const COOKIE_NAME: &str = "roa.sid";
+
+/// Default max age of sessions.
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Length of session ids generated by `MemoryStore`.
+const ID_LENGTH: usize = 32;
+
+/// The data of a session.
+pub type SessionData = Map<String, Value>;
+
+/// Throw a internal server error.
+#[inline]
+fn manager_not_set() -> Status {
+    Status::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "middleware `SessionManager` is not set correctly",
+        false,
+    )
+}
+
+/// A storage of sessions.
+///
+/// The value of the session cookie is decided by the store,
+/// it may be a session id of a server-side store or the session data of a client-side store.
+/// Stores get the context, so they can use connections in the state, like pools of roa-pg or roa-diesel.
+#[async_trait(?Send)]
+pub trait SessionStore<S = ()>: 'static + Sync + Send {
+    /// Load session data by the value of the session cookie,
+    /// return `None` if the session doesn't exist or has expired.
+    async fn load(&self, ctx: &Context<S>, cookie: &str) -> Result<Option<SessionData>>;
+
+    /// Store session data, return the value of the session cookie.
+    ///
+    /// `cookie` is `None` if the session is new or its id should be rotated.
+    async fn store(
+        &self,
+        ctx: &Context<S>,
+        cookie: Option<&str>,
+        data: &SessionData,
+        max_age: Duration,
+    ) -> Result<String>;
+
+    /// Destroy a session by the value of the session cookie.
+    async fn destroy(&self, ctx: &Context<S>, cookie: &str) -> Result;
+}
+
+/// A session, modified by typed get/insert/remove.
+///
+/// Changed sessions are persisted after downstream middleware returns.
+#[derive(Debug, Default)]
+pub struct Session {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    data: SessionData,
+    changed: bool,
+    renew: bool,
+}
+
+impl Session {
+    fn new(data: SessionData) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                data,
+                changed: false,
+                renew: false,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Get a value, return `None` if the key doesn't exist.
+    pub fn get<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        match self.lock().data.get(key) {
+            Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Insert a value, return the old one.
+    pub fn insert<T>(&self, key: impl Into<String>, value: T) -> Result<Option<Value>>
+    where
+        T: Serialize,
+    {
+        let value = serde_json::to_value(value)?;
+        let mut inner = self.lock();
+        inner.changed = true;
+        Ok(inner.data.insert(key.into(), value))
+    }
+
+    /// Remove a value, return it.
+    pub fn remove(&self, key: &str) -> Option<Value> {
+        let mut inner = self.lock();
+        let value = inner.data.remove(key);
+        inner.changed |= value.is_some();
+        value
+    }
+
+    /// Remove all values.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.changed |= !inner.data.is_empty();
+        inner.data.clear();
+    }
+
+    /// Rotate the session id, keeping the data.
+    ///
+    /// Call this method after login or privilege changes to prevent session fixation.
+    pub fn renew(&self) {
+        self.lock().renew = true;
+    }
+
+    /// Destroy the session, the session cookie is removed unless new values are inserted.
+    pub fn destroy(&self) {
+        let mut inner = self.lock();
+        inner.data.clear();
+        inner.changed = true;
+        inner.renew = true;
+    }
+
+    /// Return true if the session has no value.
+    pub fn is_empty(&self) -> bool {
+        self.lock().data.is_empty()
+    }
+}
+
+/// A context extension.
+/// This extension must be used in downstream of middleware `SessionManager`,
+/// otherwise you cannot get the session.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::{Context, Result};
+/// use roa::session::SessionGetter;
+///
+/// async fn count(ctx: &mut Context) -> Result {
+///     let session = ctx.session()?;
+///     let count: u64 = session.get("count")?.unwrap_or_default();
+///     session.insert("count", count + 1)?;
+///     Ok(())
+/// }
+/// ```
+pub trait SessionGetter {
+    /// Get the session, throw 500 INTERNAL SERVER ERROR if `SessionManager` is not set.
+    fn session(&self) -> Result<Arc<Session>>;
+}
+
+impl<S> SessionGetter for Context<S> {
+    #[inline]
+    fn session(&self) -> Result<Arc<Session>> {
+        self.load_scoped::<SessionScope, Session>(SESSION)
+            .map(|session| session.value())
+            .ok_or_else(manager_not_set)
+    }
+}
+
+/// A middleware to load and persist sessions by cookie.
+///
+/// The session is loaded by the session cookie before downstream middleware is called,
+/// and it's persisted after that if it's changed or renewed.
+/// Empty sessions are not persisted, changes are discarded if downstream returns an error.
+pub struct SessionManager<T> {
+    store: T,
+    name: String,
+    max_age: Duration,
+    path: String,
+    domain: Option<String>,
+    secure: bool,
+    same_site: SameSite,
+}
+
+impl<T> SessionManager<T> {
+    /// Construct a manager with a store.
+    ///
+    /// The session cookie is named "roa.sid", its path is "/", its max age is one day,
+    /// it's http-only and its same-site policy is lax.
+    pub fn new(store: T) -> Self {
+        Self {
+            store,
+            name: COOKIE_NAME.to_string(),
+            max_age: MAX_AGE,
+            path: "/".to_string(),
+            domain: None,
+            secure: false,
+            same_site: SameSite::Lax,
+        }
+    }
+
+    /// Set the name of the session cookie.
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the max age of sessions.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Set the path of the session cookie.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Set the domain of the session cookie.
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Send the session cookie only over https.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Set the same-site policy of the session cookie.
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Get the value of the session cookie in request,
+    /// cookies are parsed as `cookie_parser` does, so it's not required upstream.
+    fn get_cookie<S>(&self, ctx: &mut Context<S>) -> Option<String> {
+        parse_cookies(ctx);
+        ctx.cookie(&self.name)
+            .map(|cookie| cookie.value().to_string())
+    }
+
+    /// Build the session cookie.
+    fn cookie(&self, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::build(self.name.clone(), value)
+            .path(self.path.clone())
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .max_age(time::Duration::seconds(self.max_age.as_secs() as i64))
+            .finish();
+        if let Some(ref domain) = self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        cookie
+    }
+
+    /// Persist the session if it's changed or renewed.
+    async fn persist<S>(&self, ctx: &mut Context<S>, cookie: Option<String>) -> Result
+    where
+        T: SessionStore<S>,
+    {
+        let session = ctx.session()?;
+        let (data, renew) = {
+            let mut inner = session.lock();
+            if !inner.changed && !inner.renew {
+                return Ok(());
+            }
+            (std::mem::take(&mut inner.data), inner.renew)
+        };
+        let exists = cookie.is_some();
+        let cookie = match cookie {
+            Some(cookie) if renew || data.is_empty() => {
+                self.store.destroy(ctx, &cookie).await?;
+                None
+            }
+            cookie => cookie,
+        };
+        if data.is_empty() {
+            if exists {
+                ctx.set_cookie(removal(self.cookie(String::new())))?;
+            }
+            return Ok(());
+        }
+        let value = self
+            .store
+            .store(ctx, cookie.as_deref(), &data, self.max_age)
+            .await?;
+        ctx.set_cookie(self.cookie(value))
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, S, T> Middleware<'a, S> for SessionManager<T>
+where
+    T: SessionStore<S>,
+{
+    #[inline]
+    async fn handle(&'a self, ctx: &'a mut Context<S>, next: Next<'a>) -> Result {
+        let mut cookie = self.get_cookie(ctx);
+        let data = match cookie {
+            Some(ref value) => self.store.load(ctx, value).await?,
+            None => None,
+        };
+        if data.is_none() {
+            // ignore unknown session ids, new ones are generated.
+            cookie = None;
+        }
+        ctx.store_scoped(
+            SessionScope,
+            SESSION,
+            Session::new(data.unwrap_or_default()),
+        );
+        // changes are discarded if downstream fails.
+        next.await?;
+        self.persist(ctx, cookie).await
+    }
+}
+
+/// A server-side store keeping sessions in memory.
+///
+/// Sessions are lost after restart and not shared between processes,
+/// it's suitable for development and single-process deployments.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    sessions: Arc<Mutex<HashMap<String, (SessionData, Instant)>>>,
+}
+
+impl MemoryStore {
+    /// Construct an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (SessionData, Instant)>> {
+        self.sessions.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Get the number of alive sessions.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.lock()
+            .values()
+            .filter(|(_, expires)| *expires > now)
+            .count()
+    }
+
+    /// Return true if there is no alive session.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait(?Send)]
+impl<S> SessionStore<S> for MemoryStore {
+    async fn load(
+        &self,
+        _ctx: &Context<S>,
+        cookie: &str,
+    ) -> Result<Option<SessionData>> {
+        let mut sessions = self.lock();
+        match sessions.get(cookie) {
+            Some((data, expires)) if *expires > Instant::now() => Ok(Some(data.clone())),
+            Some(_) => {
+                sessions.remove(cookie);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn store(
+        &self,
+        _ctx: &Context<S>,
+        cookie: Option<&str>,
+        data: &SessionData,
+        max_age: Duration,
+    ) -> Result<String> {
+        let id = match cookie {
+            Some(id) => id.to_string(),
+            None => rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(ID_LENGTH)
+                .collect(),
+        };
+        let now = Instant::now();
+        let mut sessions = self.lock();
+        sessions.retain(|_, (_, expires)| *expires > now);
+        sessions.insert(id.clone(), (data.clone(), now + max_age));
+        Ok(id)
+    }
+
+    async fn destroy(&self, _ctx: &Context<S>, cookie: &str) -> Result {
+        self.lock().remove(cookie);
+        Ok(())
+    }
+}
+
+/// A client-side store keeping sessions in signed cookies.
+///
+/// The session data is serialized into the session cookie and signed by the key,
+/// so the client cannot modify it, but can read it.
+/// Don't put secrets in this store, and keep sessions small as cookies are limited to 4KB.
+///
+/// Destroyed sessions cannot be revoked on the client,
+/// an expiration is signed with the data to limit replay.
+#[derive(Clone)]
+pub struct CookieStore {
+    key: Key,
+}
+
+impl CookieStore {
+    /// Construct a store signing cookies by the key.
+    pub fn new(key: Key) -> Self {
+        Self { key }
+    }
+}
+
+/// Get seconds since unix epoch.
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[async_trait(?Send)]
+impl<S> SessionStore<S> for CookieStore {
+    async fn load(
+        &self,
+        _ctx: &Context<S>,
+        cookie: &str,
+    ) -> Result<Option<SessionData>> {
+        let mut jar = CookieJar::new();
+        jar.add_original(Cookie::new(SESSION, cookie.to_string()));
+        let value = match jar.signed(&self.key).get(SESSION) {
+            Some(cookie) => cookie.value().to_string(),
+            None => return Ok(None),
+        };
+        let mut payload: Value = match serde_json::from_str(&value) {
+            Ok(payload) => payload,
+            Err(_) => return Ok(None),
+        };
+        let alive = payload["expires"].as_u64().unwrap_or_default() > unix_timestamp();
+        match payload["data"].take() {
+            Value::Object(data) if alive => Ok(Some(data)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn store(
+        &self,
+        _ctx: &Context<S>,
+        _cookie: Option<&str>,
+        data: &SessionData,
+        max_age: Duration,
+    ) -> Result<String> {
+        let payload = json!({
+            "expires": unix_timestamp() + max_age.as_secs(),
+            "data": data,
+        });
+        let mut jar = CookieJar::new();
+        jar.signed(&self.key)
+            .add(Cookie::new(SESSION, serde_json::to_string(&payload)?));
+        match jar.get(SESSION) {
+            Some(cookie) => Ok(cookie.value().to_string()),
+            None => Err(Status::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "fail to sign session cookie",
+                false,
+            )),
+        }
+    }
+
+    async fn destroy(&self, _ctx: &Context<S>, _cookie: &str) -> Result {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::{CookieStore, Key, MemoryStore, SessionGetter, SessionManager};
+    use crate::http::header::{COOKIE, SET_COOKIE};
+    use crate::http::StatusCode;
+    use crate::preload::*;
+    use crate::{throw, App, Context};
+    use async_std::task::spawn;
+
+    async fn end(ctx: &mut Context) -> crate::Result {
+        let session = ctx.session()?;
+        match ctx.uri().path() {
+            "/login" => {
+                session.insert("user", "Hexilee")?;
+                session.renew();
+            }
+            "/logout" => session.destroy(),
+            "/fail" => {
+                session.insert("user", "Hexilee")?;
+                throw!(StatusCode::BAD_REQUEST)
+            }
+            _ => {
+                let user: Option<String> = session.get("user")?;
+                ctx.write(user.unwrap_or_default());
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the session cookie in response.
+    fn session_cookie(resp: &reqwest::Response) -> Option<String> {
+        resp.headers()
+            .get(SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(ToString::to_string)
+    }
+
+    #[tokio::test]
+    async fn memory_store() -> Result<(), Box<dyn std::error::Error>> {
+        let store = MemoryStore::new();
+        let (addr, server) = App::new()
+            .gate(SessionManager::new(store.clone()))
+            .end(end)
+            .run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        // empty sessions are not persisted.
+        let resp = client.get(&format!("http://{}/", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert!(session_cookie(&resp).is_none());
+        assert!(store.is_empty());
+
+        // changes are discarded on errors.
+        let resp = client.get(&format!("http://{}/fail", addr)).send().await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert!(session_cookie(&resp).is_none());
+        assert!(store.is_empty());
+
+        // unknown ids are ignored.
+        let resp = client
+            .get(&format!("http://{}/login", addr))
+            .header(COOKIE, "roa.sid=fixed")
+            .send()
+            .await?;
+        let first = session_cookie(&resp).unwrap();
+        assert_ne!("roa.sid=fixed", first);
+        assert!(resp.headers()[SET_COOKIE].to_str()?.contains("HttpOnly"));
+        assert_eq!(1, store.len());
+
+        let resp = client
+            .get(&format!("http://{}/", addr))
+            .header(COOKIE, &first)
+            .send()
+            .await?;
+        assert!(session_cookie(&resp).is_none());
+        assert_eq!("Hexilee", resp.text().await?);
+
+        // ids are rotated after login.
+        let resp = client
+            .get(&format!("http://{}/login", addr))
+            .header(COOKIE, &first)
+            .send()
+            .await?;
+        let second = session_cookie(&resp).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(1, store.len());
+        let resp = client
+            .get(&format!("http://{}/", addr))
+            .header(COOKIE, &first)
+            .send()
+            .await?;
+        assert_eq!("", resp.text().await?);
+
+        let resp = client
+            .get(&format!("http://{}/logout", addr))
+            .header(COOKIE, &second)
+            .send()
+            .await?;
+        assert_eq!("roa.sid=", session_cookie(&resp).unwrap());
+        assert!(resp.headers()[SET_COOKIE].to_str()?.contains("Max-Age=0"));
+        assert!(store.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cookie_store() -> Result<(), Box<dyn std::error::Error>> {
+        let manager = SessionManager::new(CookieStore::new(Key::generate()));
+        let (addr, server) = App::new().gate(manager).end(end).run()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        let resp = client.get(&format!("http://{}/login", addr)).send().await?;
+        let cookie = session_cookie(&resp).unwrap();
+        let resp = client
+            .get(&format!("http://{}/", addr))
+            .header(COOKIE, &cookie)
+            .send()
+            .await?;
+        assert_eq!("Hexilee", resp.text().await?);
+
+        // tampered cookies are ignored.
+        let tampered = cookie.replace("Hexilee", "Admin");
+        assert_ne!(cookie, tampered);
+        let resp = client
+            .get(&format!("http://{}/", addr))
+            .header(COOKIE, &tampered)
+            .send()
+            .await?;
+        assert_eq!("", resp.text().await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+//! This module provides a Server-Sent Events endpoint `Sse` and a context extension `LastEventId`.
+//!
+//! ### Example
+//!
+//! ```
+//! use futures::channel::mpsc::unbounded;
+//! use roa::router::{Router, RouterError};
+//! use roa::sse::{Event, LastEventId, Sse};
+//! use roa::{App, Context};
+//!
+//! # fn main() -> Result<(), RouterError> {
+//! let router = Router::new().on("/clock", Sse::new(|ctx: Context| {
+//!     let (sender, receiver) = unbounded();
+//!     let start: u64 = ctx
+//!         .last_event_id()
+//!         .and_then(|id| id.parse().ok())
+//!         .unwrap_or(0);
+//!     for id in start + 1..=start + 3 {
+//!         let event = Event::new("tick").id(id.to_string()).event("clock");
+//!         sender.unbounded_send(event).unwrap();
+//!     }
+//!     receiver
+//! }));
+//! let app = App::new().end(router.routes("/")?);
+//! Ok(())
+//! # }
+//! ```
+
+use crate::http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use crate::http::StatusCode;
+use crate::{async_trait, Context, Endpoint, Result, State};
+use bytes::Bytes;
+use futures::Stream;
+use futures_timer::Delay;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use std::time::Duration;
+
+/// Default interval of keep-alive comments.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Name of the request header of last event id.
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// A private scope to store last event id in Context::storage,
+/// as the context passed to task has no request.
+struct SseScope;
+
+/// Keep-alive comment.
+const KEEP_ALIVE_COMMENT: &[u8] = b": keep-alive\n\n";
+
+/// An event of Server-Sent Events.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Event {
+    /// Field `id`, the client sends it back in header `Last-Event-ID` when reconnecting.
+    pub id: Option<String>,
+
+    /// Field `event`, the type of event.
+    pub event: Option<String>,
+
+    /// Field `data`, multiple lines are sent as multiple `data` fields.
+    pub data: String,
+
+    /// Field `retry`, the reconnection time.
+    pub retry: Option<Duration>,
+}
+
+impl Event {
+    /// Construct an event with data.
+    pub fn new(data: impl Into<String>) -> Self {
+        Self {
+            data: data.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Set id.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Set type of event.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Set reconnection time.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Encode to a frame.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut frame = String::new();
+        if let Some(ref id) = self.id {
+            frame.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        if let Some(ref event) = self.event {
+            frame.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(retry) = self.retry {
+            frame.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        // every line break starts a new data line, including a trailing one.
+        let data = self.data.replace("\r\n", "\n").replace('\r', "\n");
+        for line in data.split('\n') {
+            if line.is_empty() {
+                frame.push_str("data:\n");
+            } else {
+                frame.push_str(&format!("data: {}\n", line));
+            }
+        }
+        frame.push('\n');
+        frame.into()
+    }
+}
+
+/// Remove line breaks, which would start a new field.
+fn single_line(value: &str) -> String {
+    value.replace(&['\r', '\n'][..], "")
+}
+
+/// A context extension to get header `Last-Event-ID` sent by reconnecting clients.
+pub trait LastEventId {
+    /// Get header `Last-Event-ID`, return `None` if it's not set.
+    fn last_event_id(&self) -> Option<String>;
+}
+
+impl<S> LastEventId for Context<S> {
+    #[inline]
+    fn last_event_id(&self) -> Option<String> {
+        match self.load_scoped::<SseScope, String>(LAST_EVENT_ID) {
+            Some(id) => Some(id.to_string()),
+            None => self.get(LAST_EVENT_ID).map(ToString::to_string),
+        }
+    }
+}
+
+/// The Server-Sent Events endpoint.
+///
+/// ### Parameter
+///
+/// - Context<S>
+///
+/// The context is the same with roa context,
+/// however, write anything to response is unavailing.
+///
+/// ### Return
+///
+/// A `Stream` of `Event`, like the receiver of a channel.
+/// The response ends when the stream ends.
+///
+/// The response is of type `text/event-stream` and never compressed by `roa::compress::Compress`.
+pub struct Sse<F, S, St> {
+    task: F,
+    keep_alive: Option<Duration>,
+    _task: PhantomData<fn(Context<S>) -> St>,
+}
+
+impl<F, S, St> Sse<F, S, St>
+where
+    F: Fn(Context<S>) -> St,
+{
+    /// Construct a Server-Sent Events endpoint by task closure.
+    pub fn new(task: F) -> Self {
+        Self {
+            task,
+            keep_alive: Some(KEEP_ALIVE),
+            _task: PhantomData,
+        }
+    }
+
+    /// Set interval of keep-alive comments, 15 seconds by default, `None` to disable them.
+    pub fn keep_alive(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.keep_alive = interval.into();
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, F, S, St> Endpoint<'a, S> for Sse<F, S, St>
+where
+    S: State,
+    F: 'static + Sync + Send + Fn(Context<S>) -> St,
+    St: 'static + Sync + Send + Stream<Item = Event>,
+{
+    #[inline]
+    async fn call(&'a self, ctx: &'a mut Context<S>) -> Result {
+        if let Some(id) = ctx.get(LAST_EVENT_ID).map(ToString::to_string) {
+            ctx.store_scoped(SseScope, LAST_EVENT_ID, id);
+        }
+        let events = (self.task)(ctx.clone());
+        ctx.resp.status = StatusCode::OK;
+        ctx.resp
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        ctx.resp
+            .headers
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        ctx.resp.write_stream(EventStream {
+            events: Box::pin(events),
+            keep_alive: self.keep_alive,
+            timer: self.keep_alive.map(Delay::new),
+        });
+        Ok(())
+    }
+}
+
+/// A stream to encode events and insert keep-alive comments.
+struct EventStream<St> {
+    events: Pin<Box<St>>,
+    keep_alive: Option<Duration>,
+    timer: Option<Delay>,
+}
+
+impl<St> EventStream<St> {
+    /// Restart the keep-alive timer.
+    fn reset(&mut self) {
+        if let (Some(timer), Some(interval)) = (self.timer.as_mut(), self.keep_alive) {
+            timer.reset(interval);
+        }
+    }
+}
+
+impl<St> Stream for EventStream<St>
+where
+    St: Stream<Item = Event>,
+{
+    type Item = io::Result<Bytes>;
+    #[inline]
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        match self.events.as_mut().poll_next(cx) {
+            Poll::Ready(Some(event)) => {
+                self.reset();
+                return Poll::Ready(Some(Ok(event.to_bytes())));
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => (),
+        }
+        let fired = match self.timer.as_mut() {
+            Some(timer) => Pin::new(timer).poll(cx).is_ready(),
+            None => false,
+        };
+        if fired {
+            self.reset();
+            return Poll::Ready(Some(Ok(Bytes::from_static(KEEP_ALIVE_COMMENT))));
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::{Event, LastEventId, Sse};
+    use crate::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+    use crate::preload::*;
+    use crate::{App, Context};
+    use async_std::task::{sleep, spawn};
+    use futures::channel::mpsc::unbounded;
+    use futures::stream::iter;
+    use std::time::Duration;
+
+    #[test]
+    fn encode_event() {
+        let event = Event::new("first\nsecond")
+            .id("1\n")
+            .event("message")
+            .retry(Duration::from_secs(3));
+        assert_eq!(
+            "id: 1\nevent: message\nretry: 3000\ndata: first\ndata: second\n\n",
+            event.to_bytes()
+        );
+        assert_eq!("data:\n\n", Event::default().to_bytes());
+        assert_eq!(
+            "data: a\ndata: b\ndata: c\ndata:\n\n",
+            Event::new("a\rb\r\nc\n").to_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn events() -> Result<(), Box<dyn std::error::Error>> {
+        let sse = Sse::new(|ctx: Context| {
+            let start: u64 = ctx
+                .last_event_id()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(0);
+            iter((start + 1..=start + 2).map(|id| Event::new("tick").id(id.to_string())))
+        });
+        let (addr, server) = App::new().end(sse).run()?;
+        spawn(server);
+        let resp = reqwest::Client::new()
+            .get(&format!("http://{}", addr))
+            .header("last-event-id", "3")
+            .send()
+            .await?;
+        assert_eq!("text/event-stream", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("no-cache", resp.headers()[CACHE_CONTROL]);
+        assert_eq!(
+            "id: 4\ndata: tick\n\nid: 5\ndata: tick\n\n",
+            resp.text().await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_alive() -> Result<(), Box<dyn std::error::Error>> {
+        let sse = Sse::new(|_ctx: Context| {
+            let (sender, receiver) = unbounded();
+            spawn(async move {
+                sleep(Duration::from_millis(250)).await;
+                sender.unbounded_send(Event::new("done")).unwrap();
+            });
+            receiver
+        })
+        .keep_alive(Duration::from_millis(100));
+        let (addr, server) = App::new().end(sse).run()?;
+        spawn(server);
+        let text = reqwest::get(&format!("http://{}", addr))
+            .await?
+            .text()
+            .await?;
+        // at least one keep-alive is sent before the event.
+        let keep_alive = ": keep-alive\n\n";
+        let alives = text.trim_end_matches("data: done\n\n");
+        assert!(text.ends_with("data: done\n\n"));
+        assert!(!alives.is_empty());
+        assert_eq!(keep_alive.repeat(alives.len() / keep_alive.len()), alives);
+        Ok(())
+    }
+}
@@ -21,25 +21,92 @@
 //! # }
 //! ```
 
-use crate::http::header::UPGRADE;
+#[cfg(feature = "websocket-deflate")]
+mod deflate;
+mod hub;
+mod io;
+mod managed;
+mod outbox;
+#[cfg(any(feature = "json", feature = "msgpack"))]
+mod typed;
+
+#[cfg(feature = "websocket-deflate")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "websocket-deflate")))]
+#[doc(inline)]
+pub use deflate::DeflateConfig;
+
+#[doc(inline)]
+pub use io::SocketIo;
+
+#[doc(inline)]
+pub use hub::{ConnectionId, Hub, HubSocket, Overflow};
+
+#[doc(inline)]
+pub use managed::{CloseReason, ManagedSocket};
+
+#[cfg(any(feature = "json", feature = "msgpack"))]
+#[cfg_attr(feature = "docs", doc(cfg(any(feature = "json", feature = "msgpack"))))]
+#[doc(inline)]
+pub use typed::{TypedError, TypedSocket};
+
+#[cfg(feature = "websocket-deflate")]
+use crate::http::header::SEC_WEBSOCKET_EXTENSIONS;
+use crate::http::header::{HeaderValue, ORIGIN, SEC_WEBSOCKET_PROTOCOL, UPGRADE};
 use crate::http::StatusCode;
 use crate::{async_trait, throw, Context, Endpoint, State, Status};
 use headers::{
     Connection, HeaderMapExt, SecWebsocketAccept, SecWebsocketKey, SecWebsocketVersion,
     Upgrade,
 };
-use hyper::upgrade::Upgraded;
+use std::collections::HashSet;
 use std::future::Future;
 use std::marker::PhantomData;
 use std::sync::Arc;
 pub use tokio_tungstenite::tungstenite::{
     self,
-    protocol::{Message, WebSocketConfig},
+    protocol::{frame::coding::CloseCode, CloseFrame, Message, WebSocketConfig},
 };
 use tokio_tungstenite::WebSocketStream;
 
-/// An alias for WebSocketStream<Upgraded>.
-pub type SocketStream = WebSocketStream<Upgraded>;
+/// An alias for WebSocketStream<SocketIo>.
+pub type SocketStream = WebSocketStream<SocketIo>;
+
+/// A private scope to store the selected subprotocol in Context::storage.
+struct WebsocketScope;
+
+/// Key of the selected subprotocol.
+const PROTOCOL: &str = "protocol";
+
+/// A context extension to get the subprotocol selected in handshake.
+///
+/// ### Example
+/// ```
+/// use futures::StreamExt;
+/// use roa::websocket::{SubProtocol, Websocket};
+/// use roa::Context;
+///
+/// let websocket = Websocket::new(|ctx: Context, stream| async move {
+///     match ctx.subprotocol().as_deref() {
+///         Some("chat.v2") => println!("speak chat v2"),
+///         _ => println!("speak chat v1"),
+///     }
+///     let (write, read) = stream.split();
+///     let _ = read.forward(write).await;
+/// })
+/// .protocols(vec!["chat.v2", "chat.v1"]);
+/// ```
+pub trait SubProtocol {
+    /// Get the selected subprotocol, return `None` if no subprotocol is selected.
+    fn subprotocol(&self) -> Option<String>;
+}
+
+impl<S> SubProtocol for Context<S> {
+    #[inline]
+    fn subprotocol(&self) -> Option<String> {
+        self.load_scoped::<WebsocketScope, String>(PROTOCOL)
+            .map(|protocol| protocol.to_string())
+    }
+}
 
 /// The Websocket middleware.
 ///
@@ -84,6 +151,12 @@ where
 {
     task: Arc<F>,
     config: Option<WebSocketConfig>,
+    // supported subprotocols, in order of preference.
+    protocols: Vec<String>,
+    // allowed origins, in lowercase without trailing slash.
+    origins: Option<HashSet<String>>,
+    #[cfg(feature = "websocket-deflate")]
+    deflate: Option<DeflateConfig>,
     _s: PhantomData<S>,
     _fut: PhantomData<Fut>,
 }
@@ -105,6 +178,10 @@ where
         Self {
             task: Arc::new(task),
             config,
+            protocols: Vec::new(),
+            origins: None,
+            #[cfg(feature = "websocket-deflate")]
+            deflate: None,
             _s: PhantomData::default(),
             _fut: PhantomData::default(),
         }
@@ -142,6 +219,83 @@ where
     pub fn with_config(config: WebSocketConfig, task: F) -> Self {
         Self::config(Some(config), task)
     }
+
+    /// Set supported subprotocols, in order of preference.
+    ///
+    /// The most preferred one requested in header `Sec-WebSocket-Protocol` is selected
+    /// and returned in the 101 response, the task can get it by `SubProtocol::subprotocol`.
+    /// If none is supported, no subprotocol is selected.
+    pub fn protocols<P>(mut self, protocols: impl IntoIterator<Item = P>) -> Self
+    where
+        P: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set allowed origins, like `https://example.com`.
+    ///
+    /// Requests with header `Origin` not in the allow-list get a 403 FORBIDDEN,
+    /// which prevents cross-site websocket hijacking.
+    /// Requests without `Origin` are sent by non-browser clients and always allowed.
+    ///
+    /// All origins are allowed by default.
+    pub fn allow_origins<O>(mut self, origins: impl IntoIterator<Item = O>) -> Self
+    where
+        O: AsRef<str>,
+    {
+        let origins = origins
+            .into_iter()
+            .map(|origin| normalize_origin(origin.as_ref()));
+        self.origins = Some(origins.collect());
+        self
+    }
+
+    /// Enable permessage-deflate compression, defined in RFC 7692.
+    ///
+    /// The extension is negotiated in header `Sec-WebSocket-Extensions`,
+    /// frames on the `SocketStream` are compressed and decompressed transparently.
+    #[cfg(feature = "websocket-deflate")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "websocket-deflate")))]
+    pub fn deflate(mut self, config: DeflateConfig) -> Self {
+        self.deflate = Some(config);
+        self
+    }
+
+    /// Check header `Origin`.
+    fn check_origin(&self, ctx: &Context<S>) -> Result<(), Status> {
+        if let (Some(origins), Some(origin)) = (&self.origins, ctx.get(ORIGIN)) {
+            if !origins.contains(&normalize_origin(origin)) {
+                throw!(
+                    StatusCode::FORBIDDEN,
+                    format!("origin `{}` is not allowed", origin)
+                )
+            }
+        }
+        Ok(())
+    }
+
+    /// Select the most preferred subprotocol requested by client.
+    fn select_protocol(&self, ctx: &Context<S>) -> Option<String> {
+        let requested: Vec<&str> = ctx
+            .req
+            .headers
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        self.protocols
+            .iter()
+            .find(|protocol| requested.contains(&protocol.as_str()))
+            .cloned()
+    }
+}
+
+/// Normalize an origin, origins are case-insensitive.
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
 }
 
 #[async_trait(?Send)]
@@ -166,10 +320,27 @@ where
         match key {
             None => throw!(StatusCode::BAD_REQUEST, "invalid websocket upgrade request"),
             Some(key) => {
+                self.check_origin(ctx)?;
+                let protocol = self.select_protocol(ctx);
+                #[cfg(feature = "websocket-deflate")]
+                let deflate = self
+                    .deflate
+                    .and_then(|config| config.negotiate(&ctx.req.headers));
+                if let Some(ref protocol) = protocol {
+                    ctx.store_scoped(WebsocketScope, PROTOCOL, protocol.clone());
+                }
                 let body = ctx.req.raw_body();
                 let context = ctx.clone();
                 let task = self.task.clone();
                 let config = self.config;
+                #[cfg(feature = "websocket-deflate")]
+                let codec = deflate.map(|params| {
+                    let max_message_size = config
+                        .unwrap_or_default()
+                        .max_message_size
+                        .unwrap_or_else(usize::max_value);
+                    params.codec(max_message_size)
+                });
                 // Setup a future that will eventually receive the upgraded
                 // connection and talk a new protocol, and spawn the future
                 // into the runtime.
@@ -181,8 +352,11 @@ where
                     match body.on_upgrade().await {
                         Err(err) => log::error!("websocket upgrade error: {}", err),
                         Ok(upgraded) => {
+                            let io = SocketIo::new(upgraded);
+                            #[cfg(feature = "websocket-deflate")]
+                            let io = io.codec(codec);
                             let websocket = WebSocketStream::from_raw_socket(
-                                upgraded,
+                                io,
                                 tungstenite::protocol::Role::Server,
                                 config,
                             )
@@ -195,8 +369,323 @@ where
                 ctx.resp.headers.typed_insert(Connection::upgrade());
                 ctx.resp.headers.typed_insert(Upgrade::websocket());
                 ctx.resp.headers.typed_insert(SecWebsocketAccept::from(key));
+                #[cfg(feature = "websocket-deflate")]
+                if let Some(deflate) = deflate {
+                    ctx.resp
+                        .headers
+                        .insert(SEC_WEBSOCKET_EXTENSIONS, deflate.header());
+                }
+                if let Some(protocol) = protocol {
+                    ctx.resp.headers.insert(
+                        SEC_WEBSOCKET_PROTOCOL,
+                        HeaderValue::from_str(&protocol)?,
+                    );
+                }
                 Ok(())
             }
         }
     }
 }
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::{CloseReason, Hub, ManagedSocket, Message, SubProtocol, Websocket};
+    use crate::tcp::Listener;
+    use crate::{App, Context};
+    use async_std::net::TcpStream;
+    use async_std::task::{sleep, spawn};
+    use futures::channel::{mpsc::unbounded, oneshot};
+    use futures::{AsyncReadExt, AsyncWriteExt, FutureExt, SinkExt, StreamExt};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    const HANDSHAKE: &str = "GET / HTTP/1.1\r\nhost: roa\r\nupgrade: websocket\r\nconnection: upgrade\r\nsec-websocket-version: 13\r\nsec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+
+    /// Read until the end of a response head.
+    async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+        let mut data = Vec::new();
+        let mut byte = [0];
+        while !data.ends_with(b"\r\n\r\n") {
+            if stream.read(&mut byte).await? == 0 {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        Ok(String::from_utf8_lossy(&data).to_lowercase())
+    }
+
+    /// Connect and finish the handshake.
+    async fn connect(addr: SocketAddr) -> std::io::Result<TcpStream> {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(format!("{}\r\n", HANDSHAKE).as_bytes())
+            .await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("http/1.1 101 switching protocols\r\n"));
+        Ok(stream)
+    }
+
+    /// Read a short unmasked frame sent by server.
+    async fn read_frame(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+        let mut head = [0; 2];
+        stream.read_exact(&mut head).await?;
+        let mut frame = vec![0; 2 + head[1] as usize];
+        frame[..2].copy_from_slice(&head);
+        stream.read_exact(&mut frame[2..]).await?;
+        Ok(frame)
+    }
+
+    #[tokio::test]
+    async fn protocol_and_origin() -> Result<(), Box<dyn std::error::Error>> {
+        let websocket = Websocket::new(|ctx: Context, mut stream| async move {
+            let protocol = ctx.subprotocol().unwrap_or_default();
+            let _ = stream.send(Message::Text(protocol)).await;
+        })
+        .protocols(vec!["chat.v2", "chat.v1"])
+        .allow_origins(vec!["https://roa.rs/"]);
+        let (addr, server) = App::new().end(websocket).run()?;
+        spawn(server);
+
+        let mut stream = TcpStream::connect(addr).await?;
+        let request = format!(
+            "{}origin: https://ROA.rs\r\nsec-websocket-protocol: chat.v1, chat.v2\r\n\r\n",
+            HANDSHAKE
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("http/1.1 101 switching protocols\r\n"));
+        assert!(head.contains("sec-websocket-protocol: chat.v2\r\n"));
+        let mut frame = [0; 9];
+        stream.read_exact(&mut frame).await?;
+        assert_eq!(b"\x81\x07chat.v2", &frame);
+
+        let mut stream = TcpStream::connect(addr).await?;
+        let request = format!("{}sec-websocket-protocol: mqtt\r\n\r\n", HANDSHAKE);
+        stream.write_all(request.as_bytes()).await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("http/1.1 101 switching protocols\r\n"));
+        assert!(!head.contains("sec-websocket-protocol"));
+        let mut frame = [0; 2];
+        stream.read_exact(&mut frame).await?;
+        assert_eq!(b"\x81\x00", &frame);
+
+        let mut stream = TcpStream::connect(addr).await?;
+        let request = format!("{}origin: https://evil.com\r\n\r\n", HANDSHAKE);
+        stream.write_all(request.as_bytes()).await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("http/1.1 403 forbidden\r\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn managed_socket() -> Result<(), Box<dyn std::error::Error>> {
+        let (sender, mut reasons) = unbounded();
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let signal = signal.map(|_| ()).shared();
+        let websocket = Websocket::new(move |_ctx, stream| {
+            let sender = sender.clone();
+            let signal = signal.clone();
+            async move {
+                let mut socket = ManagedSocket::new(stream)
+                    .ping_interval(Duration::from_millis(100))
+                    .pong_timeout(Duration::from_millis(100))
+                    .idle_timeout(Duration::from_millis(300))
+                    .shutdown(signal);
+                while let Some(Ok(_)) = socket.next().await {}
+                let _ = sender.unbounded_send(socket.close_reason().cloned());
+            }
+        });
+        let (addr, server) = App::new().end(websocket).run()?;
+        spawn(server);
+
+        // the client vanishes.
+        let mut stream = connect(addr).await?;
+        assert_eq!(b"\x89\x00", &*read_frame(&mut stream).await?);
+        assert_eq!(
+            b"\x88\x0e\x03\xf0pong timeout",
+            &*read_frame(&mut stream).await?
+        );
+        assert_eq!(Some(Some(CloseReason::PongTimeout)), reasons.next().await);
+
+        // the client replies pings but sends no message.
+        let mut stream = connect(addr).await?;
+        loop {
+            let frame = read_frame(&mut stream).await?;
+            if frame[0] == 0x89 {
+                // masked pong with zero mask.
+                stream.write_all(b"\x8a\x80\x00\x00\x00\x00").await?;
+                continue;
+            }
+            assert_eq!(b"\x88\x0e\x03\xe8idle timeout", &*frame);
+            break;
+        }
+        // masked close frame with zero mask.
+        stream
+            .write_all(b"\x88\x82\x00\x00\x00\x00\x03\xe8")
+            .await?;
+        assert_eq!(Some(Some(CloseReason::IdleTimeout)), reasons.next().await);
+
+        let mut stream = connect(addr).await?;
+        shutdown.send(()).unwrap();
+        loop {
+            let frame = read_frame(&mut stream).await?;
+            if frame[0] != 0x89 {
+                assert_eq!(b"\x88\x11\x03\xe9server shutdown", &*frame);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hub() -> Result<(), Box<dyn std::error::Error>> {
+        let hub = Hub::new();
+        let websocket = Websocket::new(|ctx: Context<Hub>, stream| async move {
+            let mut socket = ctx.connect(stream);
+            socket.join("room");
+            while let Some(Ok(_)) = socket.next().await {}
+        });
+        let (addr, server) = App::state(hub.clone()).end(websocket).run()?;
+        spawn(server);
+        let mut streams = [connect(addr).await?, connect(addr).await?];
+        while hub.members("room").len() < 2 {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(2, hub.broadcast_to("room", Message::text("hi")));
+        for stream in streams.iter_mut() {
+            assert_eq!(b"\x81\x02hi", &*read_frame(stream).await?);
+        }
+
+        for id in hub.members("room") {
+            hub.disconnect(id);
+        }
+        for stream in streams.iter_mut() {
+            assert_eq!(
+                b"\x88\x15\x03\xf0disconnected by hub",
+                &*read_frame(stream).await?
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn typed_socket() -> Result<(), Box<dyn std::error::Error>> {
+        use super::{CloseCode, TypedSocket};
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Deserialize, Serialize)]
+        struct User {
+            name: String,
+        }
+
+        let (sender, mut codes) = unbounded();
+        let websocket = Websocket::new(move |_ctx, stream| {
+            let sender = sender.clone();
+            async move {
+                let mut socket = TypedSocket::<User, User>::json(stream);
+                while let Some(result) = socket.next().await {
+                    match result {
+                        Ok(user) => {
+                            let _ = socket.send(user).await;
+                        }
+                        Err(err) => {
+                            let _ = sender.unbounded_send(err.close_code());
+                        }
+                    }
+                }
+            }
+        });
+        let (addr, server) = App::new().end(websocket).run()?;
+        spawn(server);
+
+        let mut stream = connect(addr).await?;
+        // masked text frame with zero mask.
+        stream
+            .write_all(b"\x81\x8e\x00\x00\x00\x00{\"name\":\"roa\"}")
+            .await?;
+        assert_eq!(
+            b"\x81\x0e{\"name\":\"roa\"}",
+            &*read_frame(&mut stream).await?
+        );
+        // masked binary frame with zero mask.
+        stream.write_all(b"\x82\x80\x00\x00\x00\x00").await?;
+        assert_eq!(
+            b"\x88\x18\x03\xebunsupported frame type",
+            &*read_frame(&mut stream).await?
+        );
+        assert_eq!(Some(Some(CloseCode::Unsupported)), codes.next().await);
+
+        let mut stream = connect(addr).await?;
+        stream
+            .write_all(b"\x81\x8a\x00\x00\x00\x00{\"name\":1}")
+            .await?;
+        assert_eq!(
+            b"\x88\x11\x03\xefinvalid payload",
+            &*read_frame(&mut stream).await?
+        );
+        assert_eq!(Some(Some(CloseCode::Invalid)), codes.next().await);
+        Ok(())
+    }
+
+    #[cfg(feature = "websocket-deflate")]
+    #[tokio::test]
+    async fn deflate() -> Result<(), Box<dyn std::error::Error>> {
+        use super::DeflateConfig;
+        use flate2::{
+            Compress, Compression, Decompress, FlushCompress, FlushDecompress,
+        };
+
+        let websocket = Websocket::new(|_ctx, stream| async move {
+            let (write, read) = stream.split();
+            let _ = read.forward(write).await;
+        })
+        .deflate(DeflateConfig::new().server_no_context_takeover());
+        let (addr, server) = App::new().end(websocket).run()?;
+        spawn(server);
+
+        let mut stream = TcpStream::connect(addr).await?;
+        let request = format!(
+            "{}sec-websocket-extensions: permessage-deflate; client_max_window_bits\r\n\r\n",
+            HANDSHAKE
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let head = read_head(&mut stream).await?;
+        assert!(head.starts_with("http/1.1 101 switching protocols\r\n"));
+        assert!(head.contains(
+            "sec-websocket-extensions: permessage-deflate; server_no_context_takeover\r\n"
+        ));
+
+        let text = b"Hello, World! Hello, World! Hello, World!";
+        let mut payload = Vec::with_capacity(128);
+        Compress::new(Compression::default(), false).compress_vec(
+            text,
+            &mut payload,
+            FlushCompress::Sync,
+        )?;
+        payload.truncate(payload.len() - 4);
+        // compressed text frame, masked with zero mask.
+        let mut frame = vec![0xc1, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(&payload);
+        // uncompressed text frame.
+        frame.extend_from_slice(b"\x81\x85\x00\x00\x00\x00hello");
+        stream.write_all(&frame).await?;
+
+        for expected in [&text[..], b"hello"].iter() {
+            let frame = read_frame(&mut stream).await?;
+            // frames sent by server are compressed.
+            assert_eq!(0xc1, frame[0]);
+            let mut payload = frame[2..].to_vec();
+            payload.extend_from_slice(b"\x00\x00\xff\xff");
+            let mut output = Vec::with_capacity(128);
+            Decompress::new(false).decompress_vec(
+                &payload,
+                &mut output,
+                FlushDecompress::Sync,
+            )?;
+            assert_eq!(expected, &&*output);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,657 @@
+use crate::http::header::{HeaderMap, HeaderValue, SEC_WEBSOCKET_EXTENSIONS};
+use bytes::{Buf, BufMut, BytesMut};
+use flate2::{
+    Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status,
+};
+use hyper::upgrade::Upgraded;
+use std::cmp::min;
+use std::io;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Name of the extension.
+const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+
+/// The max window bits, also the default one.
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// Tail of a deflate block flushed in sync mode, stripped from messages.
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Size to grow buffers by.
+const CHUNK: usize = 8 * 1024;
+
+/// Size of pending output above which writes wait for the connection.
+const WRITE_LIMIT: usize = 64 * 1024;
+
+/// The config of permessage-deflate extension, defined in RFC 7692.
+///
+/// ### Example
+/// ```
+/// use futures::StreamExt;
+/// use roa::websocket::{DeflateConfig, Websocket};
+/// use roa::Context;
+///
+/// let websocket = Websocket::new(|_ctx: Context, stream| async move {
+///     // frames are compressed and decompressed transparently.
+///     let (write, read) = stream.split();
+///     let _ = read.forward(write).await;
+/// })
+/// .deflate(
+///     DeflateConfig::new()
+///         .client_max_window_bits(12)
+///         .server_no_context_takeover(),
+/// );
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DeflateConfig {
+    server_max_window_bits: u8,
+    client_max_window_bits: u8,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    level: Compression,
+}
+
+/// The parameters agreed in handshake.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(super) struct Deflate {
+    server_max_window_bits: u8,
+    client_max_window_bits: u8,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    level: Compression,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeflateConfig {
+    /// Construct a config with max windows, context takeover and default compression level.
+    pub fn new() -> Self {
+        Self {
+            server_max_window_bits: MAX_WINDOW_BITS,
+            client_max_window_bits: MAX_WINDOW_BITS,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            level: Compression::default(),
+        }
+    }
+
+    /// Set the max window bits the server compresses with.
+    ///
+    /// ### Panics
+    /// If `bits` does not fall into the range 9 ..= 15.
+    pub fn server_max_window_bits(mut self, bits: u8) -> Self {
+        assert!(
+            bits > 8 && bits <= MAX_WINDOW_BITS,
+            "bits must be within 9 ..= 15"
+        );
+        self.server_max_window_bits = bits;
+        self
+    }
+
+    /// Set the max window bits the client compresses with.
+    ///
+    /// Offers without parameter `client_max_window_bits` are declined if bits is less than 15,
+    /// as the client doesn't support limiting its window.
+    ///
+    /// ### Panics
+    /// If `bits` does not fall into the range 9 ..= 15.
+    pub fn client_max_window_bits(mut self, bits: u8) -> Self {
+        assert!(
+            bits > 8 && bits <= MAX_WINDOW_BITS,
+            "bits must be within 9 ..= 15"
+        );
+        self.client_max_window_bits = bits;
+        self
+    }
+
+    /// Reset the compression context of server after each message.
+    pub fn server_no_context_takeover(mut self) -> Self {
+        self.server_no_context_takeover = true;
+        self
+    }
+
+    /// Ask the client to reset its compression context after each message.
+    pub fn client_no_context_takeover(mut self) -> Self {
+        self.client_no_context_takeover = true;
+        self
+    }
+
+    /// Set the compression level, in the range 0 ..= 9.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Compression::new(level);
+        self
+    }
+
+    /// Accept the first acceptable offer in header `Sec-WebSocket-Extensions`.
+    pub(super) fn negotiate(&self, headers: &HeaderMap) -> Option<Deflate> {
+        headers
+            .get_all(SEC_WEBSOCKET_EXTENSIONS)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|offer| self.accept(offer))
+    }
+
+    /// Accept an offer, return `None` if it's not acceptable.
+    fn accept(&self, offer: &str) -> Option<Deflate> {
+        let mut params = offer.split(';').map(str::trim);
+        if !params.next()?.eq_ignore_ascii_case(PERMESSAGE_DEFLATE) {
+            return None;
+        }
+        let mut server_no_context_takeover = false;
+        let mut client_no_context_takeover = false;
+        let mut server_max_window_bits = None;
+        // `Some(None)` if the client supports the parameter but offers no value.
+        let mut client_max_window_bits = None;
+        for param in params {
+            let (name, value) = match param.find('=') {
+                Some(index) => (
+                    param[..index].trim(),
+                    Some(param[index + 1..].trim().trim_matches('"')),
+                ),
+                None => (param, None),
+            };
+            match (name.to_ascii_lowercase().as_str(), value) {
+                ("server_no_context_takeover", None) if !server_no_context_takeover => {
+                    server_no_context_takeover = true
+                }
+                ("client_no_context_takeover", None) if !client_no_context_takeover => {
+                    client_no_context_takeover = true
+                }
+                ("server_max_window_bits", Some(value))
+                    if server_max_window_bits.is_none() =>
+                {
+                    server_max_window_bits = Some(window_bits(value)?)
+                }
+                ("client_max_window_bits", value)
+                    if client_max_window_bits.is_none() =>
+                {
+                    client_max_window_bits = Some(match value {
+                        Some(value) => Some(window_bits(value)?),
+                        None => None,
+                    })
+                }
+                // unknown or duplicated parameters.
+                _ => return None,
+            }
+        }
+
+        let server_max_window_bits = min(
+            self.server_max_window_bits,
+            server_max_window_bits.unwrap_or(MAX_WINDOW_BITS),
+        );
+        // zlib cannot compress with a window of 8 bits.
+        if server_max_window_bits < 9 {
+            return None;
+        }
+        let client_max_window_bits = match client_max_window_bits {
+            Some(bits) => {
+                min(self.client_max_window_bits, bits.unwrap_or(MAX_WINDOW_BITS))
+            }
+            None if self.client_max_window_bits < MAX_WINDOW_BITS => return None,
+            None => MAX_WINDOW_BITS,
+        };
+        Some(Deflate {
+            server_max_window_bits,
+            client_max_window_bits,
+            server_no_context_takeover: server_no_context_takeover
+                || self.server_no_context_takeover,
+            client_no_context_takeover: client_no_context_takeover
+                || self.client_no_context_takeover,
+            level: self.level,
+        })
+    }
+}
+
+/// Parse window bits in the range 8 ..= 15.
+fn window_bits(value: &str) -> Option<u8> {
+    value
+        .parse()
+        .ok()
+        .filter(|bits| (8..=MAX_WINDOW_BITS).contains(bits))
+}
+
+impl Deflate {
+    /// The value of header `Sec-WebSocket-Extensions` in response.
+    pub(super) fn header(&self) -> HeaderValue {
+        let mut value = PERMESSAGE_DEFLATE.to_string();
+        if self.server_no_context_takeover {
+            value.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            value.push_str("; client_no_context_takeover");
+        }
+        if self.server_max_window_bits < MAX_WINDOW_BITS {
+            value.push_str(&format!(
+                "; server_max_window_bits={}",
+                self.server_max_window_bits
+            ));
+        }
+        if self.client_max_window_bits < MAX_WINDOW_BITS {
+            value.push_str(&format!(
+                "; client_max_window_bits={}",
+                self.client_max_window_bits
+            ));
+        }
+        HeaderValue::from_str(&value).expect("extension header must be valid")
+    }
+
+    /// Construct the states of compression.
+    pub(super) fn codec(self, max_message_size: usize) -> Codec {
+        Codec {
+            params: self,
+            max_message_size,
+            compress: Compress::new_with_window_bits(
+                self.level,
+                false,
+                self.server_max_window_bits,
+            ),
+            // zlib cannot decompress with a window of 8 bits, a larger window works.
+            decompress: Decompress::new_with_window_bits(
+                false,
+                self.client_max_window_bits.max(9),
+            ),
+            read_buf: BytesMut::new(),
+            read_ready: BytesMut::new(),
+            inflating: false,
+            inflated: 0,
+            write_buf: BytesMut::new(),
+            write_ready: BytesMut::new(),
+            deflating: false,
+        }
+    }
+}
+
+/// The states of permessage-deflate.
+pub(super) struct Codec {
+    params: Deflate,
+    max_message_size: usize,
+    compress: Compress,
+    decompress: Decompress,
+    // raw frames read from the connection.
+    read_buf: BytesMut,
+    // decompressed frames waiting to be read by tungstenite.
+    read_ready: BytesMut,
+    // in a compressed message from client.
+    inflating: bool,
+    // decompressed size of the current message.
+    inflated: usize,
+    // raw frames written by tungstenite.
+    write_buf: BytesMut,
+    // compressed frames waiting to be written to the connection.
+    write_ready: BytesMut,
+    // in a compressed message to client.
+    deflating: bool,
+}
+
+/// Head of a frame.
+struct Head {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    // size of the head.
+    size: usize,
+    // size of the payload.
+    payload: usize,
+}
+
+impl Head {
+    /// Parse the head of a frame, return `None` if it's incomplete.
+    ///
+    /// Fail if the payload exceeds the limit.
+    fn parse(buf: &[u8], limit: Option<usize>) -> io::Result<Option<Self>> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+        let (payload, mut size) = match buf[1] & 0x7f {
+            126 if buf.len() < 4 => return Ok(None),
+            126 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+            127 if buf.len() < 10 => return Ok(None),
+            127 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&buf[2..10]);
+                (u64::from_be_bytes(bytes), 10)
+            }
+            len => (len as u64, 2),
+        };
+        match limit {
+            Some(limit) if payload > limit as u64 => {
+                return Err(invalid_data("frame too large"))
+            }
+            _ => (),
+        }
+        let mask = if buf[1] & 0x80 == 0 {
+            None
+        } else if buf.len() < size + 4 {
+            return Ok(None);
+        } else {
+            let mut mask = [0; 4];
+            mask.copy_from_slice(&buf[size..size + 4]);
+            size += 4;
+            Some(mask)
+        };
+        Ok(Some(Self {
+            fin: buf[0] & 0x80 != 0,
+            rsv1: buf[0] & 0x40 != 0,
+            opcode: buf[0] & 0x0f,
+            mask,
+            size,
+            payload: payload as usize,
+        }))
+    }
+
+    /// Write a head, masked with zero mask if `masked` is true.
+    fn write(
+        buf: &mut BytesMut,
+        fin: bool,
+        rsv1: bool,
+        opcode: u8,
+        payload: usize,
+        masked: bool,
+    ) {
+        let mut first = opcode;
+        if fin {
+            first |= 0x80;
+        }
+        if rsv1 {
+            first |= 0x40;
+        }
+        let mask_bit = if masked { 0x80 } else { 0 };
+        buf.reserve(14 + payload);
+        buf.put_u8(first);
+        if payload < 126 {
+            buf.put_u8(mask_bit | payload as u8);
+        } else if payload <= 0xffff {
+            buf.put_u8(mask_bit | 126);
+            buf.put_u16(payload as u16);
+        } else {
+            buf.put_u8(mask_bit | 127);
+            buf.put_u64(payload as u64);
+        }
+        if masked {
+            // a zero mask leaves the payload unchanged.
+            buf.put_slice(&[0; 4]);
+        }
+    }
+}
+
+impl Codec {
+    /// Decompress a complete frame in read buffer, return false if there is none.
+    fn read_frame(&mut self) -> io::Result<bool> {
+        let head = match Head::parse(&self.read_buf, Some(self.max_message_size))? {
+            Some(head) if self.read_buf.len() >= head.size + head.payload => head,
+            _ => return Ok(false),
+        };
+        let mut frame = self.read_buf.split_to(head.size + head.payload);
+        match head.opcode {
+            0x1 | 0x2 if head.rsv1 => {
+                self.inflating = true;
+                self.inflated = 0;
+            }
+            0x0 if self.inflating && !head.rsv1 => (),
+            // control frames, uncompressed messages or invalid frames, left to tungstenite.
+            _ => {
+                self.read_ready.extend_from_slice(&frame);
+                return Ok(true);
+            }
+        }
+
+        let mut payload = frame.split_off(head.size);
+        if let Some(mask) = head.mask {
+            for (index, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[index % 4];
+            }
+        }
+        let limit = self.max_message_size - self.inflated;
+        // the output grows in `inflate`, never reserve more than the limit up front.
+        let mut output = Vec::with_capacity(min(payload.len() * 2, limit));
+        inflate(&mut self.decompress, &payload, &mut output, limit)?;
+        if head.fin {
+            inflate(&mut self.decompress, &TAIL, &mut output, limit)?;
+            self.inflating = false;
+            if self.params.client_no_context_takeover {
+                self.decompress.reset(false);
+            }
+        }
+        self.inflated += output.len();
+        Head::write(
+            &mut self.read_ready,
+            head.fin,
+            false,
+            head.opcode,
+            output.len(),
+            true,
+        );
+        self.read_ready.extend_from_slice(&output);
+        Ok(true)
+    }
+
+    /// Compress a complete frame in write buffer, return false if there is none.
+    fn write_frame(&mut self) -> io::Result<bool> {
+        let head = match Head::parse(&self.write_buf, None)? {
+            Some(head) if self.write_buf.len() >= head.size + head.payload => head,
+            _ => return Ok(false),
+        };
+        let frame = self.write_buf.split_to(head.size + head.payload);
+        match head.opcode {
+            0x1 | 0x2 => self.deflating = true,
+            0x0 if self.deflating => (),
+            _ => {
+                self.write_ready.extend_from_slice(&frame);
+                return Ok(true);
+            }
+        }
+
+        let mut output = Vec::with_capacity(head.payload / 2 + 16);
+        deflate(&mut self.compress, &frame[head.size..], &mut output)?;
+        if head.fin {
+            if output.ends_with(&TAIL) {
+                output.truncate(output.len() - TAIL.len());
+            }
+            self.deflating = false;
+            if self.params.server_no_context_takeover {
+                self.compress.reset();
+            }
+        }
+        // only the first frame of a compressed message has RSV1.
+        Head::write(
+            &mut self.write_ready,
+            head.fin,
+            head.opcode != 0x0,
+            head.opcode,
+            output.len(),
+            false,
+        );
+        self.write_ready.extend_from_slice(&output);
+        Ok(true)
+    }
+
+    /// Read decompressed frames.
+    pub(super) fn poll_read(
+        &mut self,
+        io: &mut Upgraded,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        while self.read_ready.is_empty() {
+            if self.read_frame()? {
+                continue;
+            }
+            self.read_buf.reserve(CHUNK);
+            let read = futures::ready!(
+                Pin::new(&mut *io).poll_read_buf(cx, &mut self.read_buf)
+            )?;
+            if read == 0 {
+                // the connection is closed, leave incomplete frames to tungstenite.
+                let rest = self.read_buf.split();
+                self.read_ready.unsplit(rest);
+                break;
+            }
+        }
+        let read = min(buf.len(), self.read_ready.len());
+        buf[..read].copy_from_slice(&self.read_ready[..read]);
+        self.read_ready.advance(read);
+        Poll::Ready(Ok(read))
+    }
+
+    /// Compress frames and write them.
+    pub(super) fn poll_write(
+        &mut self,
+        io: &mut Upgraded,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.write_ready.len() >= WRITE_LIMIT {
+            futures::ready!(self.poll_drain(io, cx))?;
+        }
+        self.write_buf.extend_from_slice(buf);
+        while self.write_frame()? {}
+        if let Poll::Ready(Err(err)) = self.poll_drain(io, cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    /// Write all compressed frames to the connection.
+    pub(super) fn poll_drain(
+        &mut self,
+        io: &mut Upgraded,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        while !self.write_ready.is_empty() {
+            let written =
+                futures::ready!(Pin::new(&mut *io).poll_write(cx, &self.write_ready))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_ready.advance(written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Construct an io error of invalid data.
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Decompress input in sync mode, fail if output exceeds the limit.
+fn inflate(
+    decompress: &mut Decompress,
+    mut input: &[u8],
+    output: &mut Vec<u8>,
+    limit: usize,
+) -> io::Result<()> {
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(CHUNK);
+        }
+        let (total_in, total_out) = (decompress.total_in(), decompress.total_out());
+        let status = decompress
+            .decompress_vec(input, output, FlushDecompress::Sync)
+            .map_err(invalid_data)?;
+        let consumed = (decompress.total_in() - total_in) as usize;
+        let progressed = consumed > 0 || decompress.total_out() > total_out;
+        input = &input[consumed..];
+        if output.len() > limit {
+            return Err(invalid_data("message too large"));
+        }
+        if status == Status::StreamEnd {
+            // the client ended the deflate stream, the next one starts.
+            decompress.reset(false);
+        }
+        if (input.is_empty() || !progressed) && output.len() < output.capacity() {
+            return Ok(());
+        }
+    }
+}
+
+/// Compress input in sync mode.
+fn deflate(
+    compress: &mut Compress,
+    mut input: &[u8],
+    output: &mut Vec<u8>,
+) -> io::Result<()> {
+    loop {
+        if output.capacity() - output.len() < TAIL.len() + 1 {
+            output.reserve(CHUNK);
+        }
+        let total_in = compress.total_in();
+        compress
+            .compress_vec(input, output, FlushCompress::Sync)
+            .map_err(invalid_data)?;
+        input = &input[(compress.total_in() - total_in) as usize..];
+        if input.is_empty() && output.len() < output.capacity() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeflateConfig;
+    use crate::http::header::{HeaderMap, HeaderValue, SEC_WEBSOCKET_EXTENSIONS};
+
+    fn negotiate(config: DeflateConfig, offers: &'static str) -> Option<HeaderValue> {
+        let mut headers = HeaderMap::new();
+        headers.insert(SEC_WEBSOCKET_EXTENSIONS, HeaderValue::from_static(offers));
+        config.negotiate(&headers).map(|deflate| deflate.header())
+    }
+
+    #[test]
+    fn negotiate_offers() {
+        let config = DeflateConfig::new();
+        assert_eq!(
+            Some(HeaderValue::from_static("permessage-deflate")),
+            negotiate(config, "permessage-deflate; client_max_window_bits")
+        );
+        assert_eq!(
+            Some(HeaderValue::from_static(
+                "permessage-deflate; server_no_context_takeover; server_max_window_bits=10"
+            )),
+            negotiate(
+                config,
+                "permessage-deflate; server_no_context_takeover; server_max_window_bits=10"
+            )
+        );
+        assert_eq!(None, negotiate(config, "x-webkit-deflate-frame"));
+        assert_eq!(None, negotiate(config, "permessage-deflate; unknown"));
+        assert_eq!(
+            None,
+            negotiate(config, "permessage-deflate; server_max_window_bits=16")
+        );
+        // zlib cannot compress with a window of 8 bits.
+        assert_eq!(
+            None,
+            negotiate(config, "permessage-deflate; server_max_window_bits=8")
+        );
+    }
+
+    #[test]
+    fn negotiate_config() {
+        let config = DeflateConfig::new()
+            .client_max_window_bits(10)
+            .client_no_context_takeover();
+        // the first offer is declined as the client cannot limit its window.
+        assert_eq!(
+            Some(HeaderValue::from_static(
+                "permessage-deflate; client_no_context_takeover; client_max_window_bits=10"
+            )),
+            negotiate(
+                config,
+                "permessage-deflate, permessage-deflate; client_max_window_bits=\"12\""
+            )
+        );
+        assert_eq!(None, negotiate(config, "permessage-deflate"));
+    }
+}
@@ -0,0 +1,432 @@
+use super::outbox::Outbox;
+use super::tungstenite::protocol::frame::coding::CloseCode;
+use super::tungstenite::protocol::CloseFrame;
+use super::tungstenite::Error as WsError;
+use super::{Message, SocketStream};
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use futures::{Sink, Stream};
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{self, Poll};
+
+/// Default capacity of the queue of each connection.
+const CAPACITY: usize = 64;
+
+/// Reason of the close frame sent to disconnected connections.
+const DISCONNECTED: &str = "disconnected by hub";
+
+/// The policy applied to a connection whose queue is full.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Overflow {
+    /// Drop the message for this connection.
+    Drop,
+
+    /// Disconnect this connection with close code 1008.
+    Disconnect,
+}
+
+/// Identifier of a connection in a hub.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ConnectionId(u64);
+
+/// A hub of websocket connections, supporting rooms and broadcast.
+///
+/// `Hub` is cheap to clone, clones share the same connections,
+/// so you can put it in the state and broadcast in ordinary HTTP handlers.
+///
+/// Each connection has a bounded queue, messages are dropped
+/// or the connection is disconnected when its queue is full.
+///
+/// ### Example
+/// ```
+/// use roa::router::{get, Router, RouterError};
+/// use roa::websocket::{Hub, Message, Overflow, Websocket};
+/// use roa::{App, Context, Result};
+/// use futures::StreamExt;
+///
+/// async fn notify(ctx: &mut Context<Hub>) -> Result {
+///     ctx.broadcast_to("admin", Message::text("someone knocks"));
+///     Ok(())
+/// }
+///
+/// # fn main() -> std::result::Result<(), RouterError> {
+/// let chat = Websocket::new(|ctx: Context<Hub>, stream| async move {
+///     let mut socket = ctx.connect(stream);
+///     socket.join("admin");
+///     while let Some(Ok(message)) = socket.next().await {
+///         if message.is_text() {
+///             ctx.broadcast(message);
+///         }
+///     }
+/// });
+/// let router = Router::new().on("/chat", chat).on("/notify", get(notify));
+/// let hub = Hub::new().capacity(16).overflow(Overflow::Disconnect);
+/// let app = App::state(hub).end(router.routes("/")?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Hub {
+    inner: Arc<Mutex<Registry>>,
+}
+
+/// Connections, rooms and settings shared by clones of a hub.
+struct Registry {
+    capacity: usize,
+    overflow: Overflow,
+    next_id: u64,
+    connections: HashMap<ConnectionId, Connection>,
+    rooms: HashMap<String, HashSet<ConnectionId>>,
+}
+
+/// A connection in registry.
+struct Connection {
+    sender: Sender<Message>,
+    rooms: HashSet<String>,
+}
+
+/// A websocket connected to a hub, created by `Hub::connect`.
+///
+/// `HubSocket` is a `Stream` of received messages and a `Sink` of messages.
+/// Messages sent by the hub are written when the stream is polled,
+/// so keep reading it until it ends.
+/// The connection leaves the hub when it's dropped.
+pub struct HubSocket<St = SocketStream> {
+    id: ConnectionId,
+    hub: Hub,
+    stream: St,
+    queue: Receiver<Message>,
+    // a message taken from queue, waiting to be sent.
+    outbox: Outbox,
+    // whether the connection is disconnected by hub.
+    disconnected: bool,
+}
+
+impl Hub {
+    /// Construct an empty hub, with queue capacity 64 and policy `Overflow::Drop`.
+    pub fn new() -> Self {
+        let registry = Registry {
+            capacity: CAPACITY,
+            overflow: Overflow::Drop,
+            next_id: 0,
+            connections: HashMap::new(),
+            rooms: HashMap::new(),
+        };
+        Self {
+            inner: Arc::new(Mutex::new(registry)),
+        }
+    }
+
+    /// Set capacity of the queue of each connection, at least one message is queued.
+    ///
+    /// The setting is shared by clones, and applies to connections connected later.
+    pub fn capacity(self, capacity: usize) -> Self {
+        self.lock().capacity = capacity;
+        self
+    }
+
+    /// Set the policy applied to a connection whose queue is full.
+    ///
+    /// The setting is shared by clones.
+    pub fn overflow(self, overflow: Overflow) -> Self {
+        self.lock().overflow = overflow;
+        self
+    }
+
+    /// Connect a websocket stream, like `SocketStream` or `ManagedSocket`.
+    pub fn connect<St>(&self, stream: St) -> HubSocket<St> {
+        let mut registry = self.lock();
+        // the channel holds one more message than its buffer, for the only sender.
+        let (sender, queue) = channel(registry.capacity.saturating_sub(1));
+        let id = ConnectionId(registry.next_id);
+        registry.next_id += 1;
+        registry.connections.insert(
+            id,
+            Connection {
+                sender,
+                rooms: HashSet::new(),
+            },
+        );
+        HubSocket {
+            id,
+            hub: self.clone(),
+            stream,
+            queue,
+            outbox: Outbox::default(),
+            disconnected: false,
+        }
+    }
+
+    /// Count connections.
+    pub fn len(&self) -> usize {
+        self.lock().connections.len()
+    }
+
+    /// Whether there is no connection.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Join a connection to a room, return false if the connection doesn't exist.
+    pub fn join(&self, id: ConnectionId, room: &str) -> bool {
+        let mut registry = self.lock();
+        match registry.connections.get_mut(&id) {
+            None => false,
+            Some(connection) => {
+                connection.rooms.insert(room.to_string());
+                registry
+                    .rooms
+                    .entry(room.to_string())
+                    .or_default()
+                    .insert(id);
+                true
+            }
+        }
+    }
+
+    /// Remove a connection from a room.
+    pub fn leave(&self, id: ConnectionId, room: &str) {
+        let mut registry = self.lock();
+        if let Some(connection) = registry.connections.get_mut(&id) {
+            connection.rooms.remove(room);
+        }
+        registry.leave(id, room);
+    }
+
+    /// Get all connections in a room.
+    pub fn members(&self, room: &str) -> Vec<ConnectionId> {
+        match self.lock().rooms.get(room) {
+            Some(members) => members.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Send a message to a connection, return false if it's not queued.
+    pub fn send(&self, id: ConnectionId, message: Message) -> bool {
+        self.deliver(vec![id], message) == 1
+    }
+
+    /// Send a message to all connections, return the number of connections it's queued to.
+    pub fn broadcast(&self, message: Message) -> usize {
+        let ids = self.lock().connections.keys().cloned().collect();
+        self.deliver(ids, message)
+    }
+
+    /// Send a message to all connections in a room,
+    /// return the number of connections it's queued to.
+    pub fn broadcast_to(&self, room: &str, message: Message) -> usize {
+        self.deliver(self.members(room), message)
+    }
+
+    /// Disconnect a connection with close code 1008.
+    pub fn disconnect(&self, id: ConnectionId) {
+        self.lock().remove(id);
+    }
+
+    /// Queue a message to connections, applying overflow policy.
+    fn deliver(&self, ids: Vec<ConnectionId>, message: Message) -> usize {
+        let mut registry = self.lock();
+        let overflow = registry.overflow;
+        let mut delivered = 0;
+        for id in ids {
+            let result = match registry.connections.get_mut(&id) {
+                Some(connection) => connection.sender.try_send(message.clone()),
+                None => continue,
+            };
+            match result {
+                Ok(()) => delivered += 1,
+                Err(err) if err.is_full() && overflow == Overflow::Drop => (),
+                Err(_) => registry.remove(id),
+            }
+        }
+        delivered
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        // the registry is always consistent between method calls.
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    /// Remove a connection, its queue is closed.
+    fn remove(&mut self, id: ConnectionId) {
+        if let Some(connection) = self.connections.remove(&id) {
+            for room in connection.rooms {
+                self.leave(id, &room);
+            }
+        }
+    }
+
+    /// Remove a connection from a room, remove the room if it's empty.
+    fn leave(&mut self, id: ConnectionId, room: &str) {
+        if let Some(members) = self.rooms.get_mut(room) {
+            members.remove(&id);
+            if members.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+    }
+}
+
+impl<St> HubSocket<St> {
+    /// Get id of this connection.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Get the hub.
+    pub fn hub(&self) -> &Hub {
+        &self.hub
+    }
+
+    /// Join a room.
+    pub fn join(&self, room: &str) -> bool {
+        self.hub.join(self.id, room)
+    }
+
+    /// Leave a room.
+    pub fn leave(&self, room: &str) {
+        self.hub.leave(self.id, room)
+    }
+}
+
+impl<St> HubSocket<St>
+where
+    St: Sink<Message, Error = WsError> + Unpin,
+{
+    /// Write messages in queue.
+    fn poll_queue(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), WsError>> {
+        loop {
+            futures::ready!(self.outbox.poll_send(&mut self.stream, cx))?;
+            if self.disconnected {
+                break;
+            }
+            match Pin::new(&mut self.queue).poll_next(cx) {
+                Poll::Ready(Some(message)) => self.outbox.put(message),
+                Poll::Ready(None) => {
+                    self.disconnected = true;
+                    let frame = CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: DISCONNECTED.into(),
+                    };
+                    self.outbox.put(Message::Close(Some(frame)));
+                }
+                Poll::Pending => break,
+            }
+        }
+        self.outbox.poll_flush(&mut self.stream, cx)
+    }
+}
+
+impl<St> Stream for HubSocket<St>
+where
+    St: Stream<Item = Result<Message, WsError>> + Sink<Message, Error = WsError> + Unpin,
+{
+    type Item = Result<Message, WsError>;
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if let Poll::Ready(Err(err)) = self.poll_queue(cx) {
+            return Poll::Ready(Some(Err(err)));
+        }
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+impl<St> Sink<Message> for HubSocket<St>
+where
+    St: Sink<Message, Error = WsError> + Unpin,
+{
+    type Error = WsError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_queue(cx))?;
+        Pin::new(&mut self.stream).poll_ready(cx)
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        message: Message,
+    ) -> Result<(), Self::Error> {
+        Pin::new(&mut self.stream).start_send(message)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_queue(cx))?;
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_queue(cx))?;
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
+
+impl<St> Drop for HubSocket<St> {
+    fn drop(&mut self) {
+        self.hub.lock().remove(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hub, Message, Overflow};
+
+    #[test]
+    fn rooms() {
+        let hub = Hub::new();
+        let first = hub.connect(());
+        let second = hub.connect(());
+        assert_eq!(2, hub.len());
+        assert!(first.join("admin"));
+        assert!(second.join("admin"));
+        second.leave("admin");
+        assert_eq!(vec![first.id()], hub.members("admin"));
+        assert_eq!(1, hub.broadcast_to("admin", Message::text("admin")));
+        assert_eq!(2, hub.broadcast(Message::text("all")));
+        assert!(hub.send(second.id(), Message::text("second")));
+
+        let id = first.id();
+        drop(first);
+        assert_eq!(1, hub.len());
+        assert!(hub.members("admin").is_empty());
+        assert!(!hub.join(id, "admin"));
+    }
+
+    #[test]
+    fn overflow() {
+        let hub = Hub::new().capacity(2);
+        let _socket = hub.connect(());
+        assert_eq!(1, hub.broadcast(Message::text("first")));
+        assert_eq!(1, hub.broadcast(Message::text("second")));
+        assert_eq!(0, hub.broadcast(Message::text("dropped")));
+        assert_eq!(1, hub.len());
+
+        // settings are shared by clones.
+        let _ = hub.clone().capacity(1).overflow(Overflow::Disconnect);
+        let _socket = hub.connect(());
+        assert_eq!(2, hub.len());
+        assert_eq!(1, hub.broadcast(Message::text("first")));
+        assert_eq!(0, hub.broadcast(Message::text("disconnect")));
+        assert!(hub.is_empty());
+    }
+}
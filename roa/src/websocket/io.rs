@@ -0,0 +1,97 @@
+#[cfg(feature = "websocket-deflate")]
+use super::deflate::Codec;
+use hyper::upgrade::Upgraded;
+use std::io;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// The io of websocket connections.
+///
+/// If permessage-deflate is negotiated, data frames are decompressed
+/// before tungstenite reads them and compressed after tungstenite writes them.
+pub struct SocketIo {
+    io: Upgraded,
+    #[cfg(feature = "websocket-deflate")]
+    codec: Option<Box<Codec>>,
+}
+
+impl SocketIo {
+    /// Construct a socket io.
+    pub(super) fn new(io: Upgraded) -> Self {
+        Self {
+            io,
+            #[cfg(feature = "websocket-deflate")]
+            codec: None,
+        }
+    }
+
+    /// Compress frames if deflate is negotiated.
+    #[cfg(feature = "websocket-deflate")]
+    pub(super) fn codec(mut self, codec: Option<Codec>) -> Self {
+        self.codec = codec.map(Box::new);
+        self
+    }
+}
+
+impl AsyncRead for SocketIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        #[cfg(feature = "websocket-deflate")]
+        {
+            if let Some(codec) = &mut this.codec {
+                return codec.poll_read(&mut this.io, cx, buf);
+            }
+        }
+        Pin::new(&mut this.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for SocketIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        #[cfg(feature = "websocket-deflate")]
+        {
+            if let Some(codec) = &mut this.codec {
+                return codec.poll_write(&mut this.io, cx, buf);
+            }
+        }
+        Pin::new(&mut this.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        #[cfg(feature = "websocket-deflate")]
+        {
+            if let Some(codec) = &mut this.codec {
+                futures::ready!(codec.poll_drain(&mut this.io, cx))?;
+            }
+        }
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        #[cfg(feature = "websocket-deflate")]
+        {
+            if let Some(codec) = &mut this.codec {
+                futures::ready!(codec.poll_drain(&mut this.io, cx))?;
+            }
+        }
+        Pin::new(&mut this.io).poll_shutdown(cx)
+    }
+}
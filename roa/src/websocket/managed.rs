@@ -0,0 +1,344 @@
+use super::outbox::Outbox;
+use super::tungstenite::protocol::frame::coding::CloseCode;
+use super::tungstenite::protocol::CloseFrame;
+use super::tungstenite::Error as WsError;
+use super::{Message, SocketStream};
+use futures::future::poll_fn;
+use futures::{Future, Sink, Stream};
+use futures_timer::Delay;
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use std::time::Duration;
+
+/// Default interval of pings.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default timeout of pongs, also used as timeout of close handshakes.
+const PONG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The reason why a managed socket is closed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CloseReason {
+    /// The client closed the connection, with the close frame it sent.
+    ///
+    /// The frame is `None` if the client sent no frame or vanished.
+    Client(Option<CloseFrame<'static>>),
+
+    /// The task closed the connection by `ManagedSocket::close`.
+    Server(CloseFrame<'static>),
+
+    /// No pong was received in pong timeout after a ping, closed with code 1008.
+    PongTimeout,
+
+    /// No data message was sent or received in idle timeout, closed with code 1000.
+    IdleTimeout,
+
+    /// The shutdown signal fired, closed with code 1001.
+    Shutdown,
+}
+
+/// A wrapper of `SocketStream` handling heartbeat, timeouts and close handshakes.
+///
+/// `ManagedSocket` is a `Stream` of data messages and a `Sink` of messages.
+/// Pings are sent at intervals, pongs and close frames are handled internally.
+/// The socket sends a close frame and waits for the reply of the client
+/// when it times out, the shutdown signal fires or `close` is called.
+/// The stream ends when the close handshake finishes, the client vanishes
+/// or the client doesn't reply the close frame in pong timeout.
+///
+/// ### Example
+/// ```
+/// use futures::{SinkExt, StreamExt};
+/// use roa::websocket::{ManagedSocket, Websocket};
+/// use roa::Context;
+/// use std::time::Duration;
+///
+/// let websocket = Websocket::new(|_ctx: Context, stream| async move {
+///     let mut socket = ManagedSocket::new(stream)
+///         .ping_interval(Duration::from_secs(20))
+///         .idle_timeout(Duration::from_secs(300));
+///     // echo
+///     while let Some(Ok(message)) = socket.next().await {
+///         if socket.send(message).await.is_err() {
+///             break;
+///         }
+///     }
+///     println!("socket closed: {:?}", socket.close_reason());
+/// });
+/// ```
+pub struct ManagedSocket {
+    stream: SocketStream,
+    ping_interval: Option<Duration>,
+    pong_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    shutdown: Option<Pin<Box<dyn 'static + Send + Future<Output = ()>>>>,
+    started: bool,
+    ping_timer: Option<Delay>,
+    // running while waiting for a pong.
+    pong_timer: Option<Delay>,
+    idle_timer: Option<Delay>,
+    // running while waiting for the close frame of client.
+    close_timer: Option<Delay>,
+    // a control message waiting to be sent.
+    outbox: Outbox,
+    closing: bool,
+    reason: Option<CloseReason>,
+}
+
+impl ManagedSocket {
+    /// Construct a managed socket,
+    /// sending pings every 30 seconds and waiting pongs for 10 seconds.
+    pub fn new(stream: SocketStream) -> Self {
+        Self {
+            stream,
+            ping_interval: Some(PING_INTERVAL),
+            pong_timeout: PONG_TIMEOUT,
+            idle_timeout: None,
+            shutdown: None,
+            started: false,
+            ping_timer: None,
+            pong_timer: None,
+            idle_timer: None,
+            close_timer: None,
+            outbox: Outbox::default(),
+            closing: false,
+            reason: None,
+        }
+    }
+
+    /// Set interval of pings, `None` to disable them.
+    pub fn ping_interval(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.ping_interval = interval.into();
+        self
+    }
+
+    /// Set timeout of pongs and close handshakes.
+    pub fn pong_timeout(mut self, timeout: Duration) -> Self {
+        self.pong_timeout = timeout;
+        self
+    }
+
+    /// Set timeout of idle connections, disabled by default.
+    ///
+    /// A connection is idle if no data message is sent or received,
+    /// pings and pongs are not counted.
+    pub fn idle_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.idle_timeout = timeout.into();
+        self
+    }
+
+    /// Set a signal of server shutdown, like a shared receiver of a oneshot channel.
+    pub fn shutdown(
+        mut self,
+        signal: impl 'static + Send + Future<Output = ()>,
+    ) -> Self {
+        self.shutdown = Some(Box::pin(signal));
+        self
+    }
+
+    /// Get the reason why the socket is closed, return `None` if it's still open.
+    pub fn close_reason(&self) -> Option<&CloseReason> {
+        self.reason.as_ref()
+    }
+
+    /// Start the close handshake with a code and a reason.
+    ///
+    /// Keep reading the socket until it ends to finish the handshake.
+    pub async fn close(
+        &mut self,
+        code: CloseCode,
+        reason: impl Into<Cow<'static, str>>,
+    ) -> Result<(), WsError> {
+        let frame = CloseFrame {
+            code,
+            reason: reason.into(),
+        };
+        self.start_close(CloseReason::Server(frame.clone()), frame);
+        poll_fn(|cx| self.poll_pending(cx)).await
+    }
+
+    /// Start timers on first poll.
+    fn start(&mut self) {
+        if !self.started {
+            self.started = true;
+            self.ping_timer = self.ping_interval.map(Delay::new);
+            self.idle_timer = self.idle_timeout.map(Delay::new);
+        }
+    }
+
+    /// Send a close frame and wait for the reply of client.
+    fn start_close(&mut self, reason: CloseReason, frame: CloseFrame<'static>) {
+        if self.closing {
+            return;
+        }
+        self.reason = Some(reason);
+        self.outbox.put(Message::Close(Some(frame)));
+        self.stop_timers();
+        self.close_timer = Some(Delay::new(self.pong_timeout));
+    }
+
+    /// Stop timers except the close timer.
+    fn stop_timers(&mut self) {
+        self.closing = true;
+        self.ping_timer = None;
+        self.pong_timer = None;
+        self.idle_timer = None;
+        self.shutdown = None;
+    }
+
+    /// Restart the idle timer.
+    fn reset_idle(&mut self) {
+        if let (Some(timer), Some(timeout)) =
+            (self.idle_timer.as_mut(), self.idle_timeout)
+        {
+            timer.reset(timeout);
+        }
+    }
+
+    /// Poll timers and the shutdown signal.
+    fn poll_timers(&mut self, cx: &mut task::Context<'_>) {
+        let shutdown = match self.shutdown.as_mut() {
+            Some(signal) => signal.as_mut().poll(cx).is_ready(),
+            None => false,
+        };
+        if shutdown {
+            let frame = close_frame(CloseCode::Away, "server shutdown");
+            return self.start_close(CloseReason::Shutdown, frame);
+        }
+        if fired(&mut self.pong_timer, cx) {
+            let frame = close_frame(CloseCode::Policy, "pong timeout");
+            return self.start_close(CloseReason::PongTimeout, frame);
+        }
+        if fired(&mut self.idle_timer, cx) {
+            let frame = close_frame(CloseCode::Normal, "idle timeout");
+            return self.start_close(CloseReason::IdleTimeout, frame);
+        }
+        if let (true, Some(interval)) =
+            (fired(&mut self.ping_timer, cx), self.ping_interval)
+        {
+            if self.outbox.is_empty() {
+                self.outbox.put(Message::Ping(Vec::new()));
+            }
+            if self.pong_timer.is_none() {
+                self.pong_timer = Some(Delay::new(self.pong_timeout));
+                fired(&mut self.pong_timer, cx);
+            }
+            if let Some(timer) = self.ping_timer.as_mut() {
+                timer.reset(interval);
+            }
+            fired(&mut self.ping_timer, cx);
+        }
+    }
+
+    /// Send the pending control message and flush.
+    fn poll_pending(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), WsError>> {
+        self.outbox.poll(&mut self.stream, cx)
+    }
+
+    /// End the stream if the connection is closed.
+    fn on_error(&mut self, err: WsError) -> Poll<Option<Result<Message, WsError>>> {
+        match err {
+            WsError::ConnectionClosed | WsError::AlreadyClosed => {
+                self.reason.get_or_insert(CloseReason::Client(None));
+                Poll::Ready(None)
+            }
+            err => Poll::Ready(Some(Err(err))),
+        }
+    }
+}
+
+/// Construct a close frame.
+fn close_frame(code: CloseCode, reason: &'static str) -> CloseFrame<'static> {
+    CloseFrame {
+        code,
+        reason: reason.into(),
+    }
+}
+
+/// Poll a timer, return true if it fires.
+fn fired(timer: &mut Option<Delay>, cx: &mut task::Context<'_>) -> bool {
+    match timer {
+        Some(timer) => Pin::new(timer).poll(cx).is_ready(),
+        None => false,
+    }
+}
+
+impl Stream for ManagedSocket {
+    type Item = Result<Message, WsError>;
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.start();
+        loop {
+            self.poll_timers(cx);
+            if fired(&mut self.close_timer, cx) {
+                // the client doesn't reply the close frame.
+                return Poll::Ready(None);
+            }
+            if let Poll::Ready(Err(err)) = self.poll_pending(cx) {
+                return self.on_error(err);
+            }
+            let message = match futures::ready!(Pin::new(&mut self.stream).poll_next(cx))
+            {
+                None => return self.on_error(WsError::ConnectionClosed),
+                Some(Err(err)) => return self.on_error(err),
+                Some(Ok(message)) => message,
+            };
+            match message {
+                Message::Pong(_) => self.pong_timer = None,
+                // pongs are replied by tungstenite.
+                Message::Ping(_) => (),
+                // the close frame is replied by tungstenite,
+                // the next poll will get `ConnectionClosed`.
+                Message::Close(frame) => {
+                    self.reason.get_or_insert(CloseReason::Client(frame));
+                    self.stop_timers();
+                }
+                message => {
+                    self.reset_idle();
+                    return Poll::Ready(Some(Ok(message)));
+                }
+            }
+        }
+    }
+}
+
+impl Sink<Message> for ManagedSocket {
+    type Error = WsError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_pending(cx))?;
+        Pin::new(&mut self.stream).poll_ready(cx)
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        message: Message,
+    ) -> Result<(), Self::Error> {
+        if message.is_text() || message.is_binary() {
+            self.reset_idle();
+        }
+        Pin::new(&mut self.stream).start_send(message)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_pending(cx))?;
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_pending(cx))?;
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
@@ -0,0 +1,78 @@
+use super::tungstenite::Error as WsError;
+use super::Message;
+use futures::Sink;
+use std::pin::Pin;
+use std::task::{self, Poll};
+
+/// A message waiting to be sent by a socket wrapper, like a ping or a close frame.
+///
+/// The message is sent when the sink is ready, and the sink is flushed after sending.
+#[derive(Default)]
+pub(super) struct Outbox {
+    message: Option<Message>,
+    flushing: bool,
+}
+
+impl Outbox {
+    /// Whether no message is waiting.
+    pub(super) fn is_empty(&self) -> bool {
+        self.message.is_none()
+    }
+
+    /// Set the waiting message, replacing the unsent one.
+    pub(super) fn put(&mut self, message: Message) {
+        self.message = Some(message);
+    }
+
+    /// Start sending the waiting message, without flushing.
+    pub(super) fn poll_send<Si>(
+        &mut self,
+        sink: &mut Si,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), WsError>>
+    where
+        Si: Sink<Message, Error = WsError> + Unpin,
+    {
+        if let Some(message) = self.message.take() {
+            match Pin::new(&mut *sink).poll_ready(cx) {
+                Poll::Pending => {
+                    self.message = Some(message);
+                    return Poll::Pending;
+                }
+                Poll::Ready(result) => result?,
+            }
+            Pin::new(sink).start_send(message)?;
+            self.flushing = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Flush the sink if a message was sent.
+    pub(super) fn poll_flush<Si>(
+        &mut self,
+        sink: &mut Si,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), WsError>>
+    where
+        Si: Sink<Message, Error = WsError> + Unpin,
+    {
+        if self.flushing {
+            futures::ready!(Pin::new(sink).poll_flush(cx))?;
+            self.flushing = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Send the waiting message and flush.
+    pub(super) fn poll<Si>(
+        &mut self,
+        sink: &mut Si,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), WsError>>
+    where
+        Si: Sink<Message, Error = WsError> + Unpin,
+    {
+        futures::ready!(self.poll_send(sink, cx))?;
+        self.poll_flush(sink, cx)
+    }
+}
@@ -0,0 +1,307 @@
+use super::outbox::Outbox;
+use super::tungstenite::protocol::frame::coding::CloseCode;
+use super::tungstenite::protocol::CloseFrame;
+use super::tungstenite::Error as WsError;
+use super::{Message, SocketStream};
+use futures::{Sink, Stream};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{self, Poll};
+
+/// Reason of the close frame sent on unexpected frame types.
+const UNSUPPORTED: &str = "unsupported frame type";
+
+/// Reason of the close frame sent on undecodable payloads.
+const INVALID: &str = "invalid payload";
+
+/// The error of typed sockets.
+#[derive(Debug)]
+pub enum TypedError {
+    /// An error of the underlying socket.
+    Socket(WsError),
+
+    /// A frame of unexpected type was received, like a binary frame in JSON format.
+    ///
+    /// The socket is closed with code 1003.
+    Unsupported,
+
+    /// The payload cannot be decoded as the expected type.
+    ///
+    /// The socket is closed with code 1007.
+    Invalid(String),
+
+    /// The message cannot be encoded.
+    Encode(String),
+}
+
+impl TypedError {
+    /// Get the close code sent to the client, return `None` if nothing is sent.
+    pub fn close_code(&self) -> Option<CloseCode> {
+        match self {
+            TypedError::Unsupported => Some(CloseCode::Unsupported),
+            TypedError::Invalid(_) => Some(CloseCode::Invalid),
+            _ => None,
+        }
+    }
+}
+
+impl Display for TypedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TypedError::Socket(err) => write!(f, "websocket error: {}", err),
+            TypedError::Unsupported => f.write_str(UNSUPPORTED),
+            TypedError::Invalid(err) => write!(f, "{}: {}", INVALID, err),
+            TypedError::Encode(err) => write!(f, "encode error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TypedError {}
+
+impl From<WsError> for TypedError {
+    #[inline]
+    fn from(err: WsError) -> Self {
+        TypedError::Socket(err)
+    }
+}
+
+/// The format of messages.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Format {
+    /// JSON over text frames.
+    #[cfg(feature = "json")]
+    Json,
+
+    /// MessagePack over binary frames.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl Format {
+    fn encode<T: Serialize>(self, value: &T) -> Result<Message, TypedError> {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_string(value)
+                .map(Message::Text)
+                .map_err(|err| TypedError::Encode(err.to_string())),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => rmp_serde::to_vec_named(value)
+                .map(Message::Binary)
+                .map_err(|err| TypedError::Encode(err.to_string())),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, message: Message) -> Result<T, TypedError> {
+        match (self, message) {
+            #[cfg(feature = "json")]
+            (Format::Json, Message::Text(text)) => serde_json::from_str(&text)
+                .map_err(|err| TypedError::Invalid(err.to_string())),
+            #[cfg(feature = "msgpack")]
+            (Format::MessagePack, Message::Binary(data)) => {
+                rmp_serde::from_read_ref(&data)
+                    .map_err(|err| TypedError::Invalid(err.to_string()))
+            }
+            _ => Err(TypedError::Unsupported),
+        }
+    }
+}
+
+/// A wrapper of websocket streams, encoding and decoding messages by serde.
+///
+/// `TypedSocket` is a `Stream` of `In` and a `Sink` of `Out`.
+/// Pings, pongs and close frames are skipped, as they are replied by tungstenite.
+/// If a frame of unexpected type or an undecodable payload is received,
+/// the socket yields an error and starts the close handshake with code 1003 or 1007;
+/// keep reading the socket until it ends to finish the handshake.
+///
+/// The underlying stream can be a `SocketStream`, a `ManagedSocket` or a `HubSocket`.
+///
+/// ### Example
+/// ```
+/// use futures::{SinkExt, StreamExt};
+/// use roa::websocket::{TypedSocket, Websocket};
+/// use roa::Context;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize)]
+/// struct Request {
+///     name: String,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Reply {
+///     greeting: String,
+/// }
+///
+/// let websocket = Websocket::new(|_ctx: Context, stream| async move {
+///     let mut socket = TypedSocket::json(stream);
+///     while let Some(Ok(Request { name })) = socket.next().await {
+///         let greeting = format!("Hello, {}!", name);
+///         if socket.send(Reply { greeting }).await.is_err() {
+///             break;
+///         }
+///     }
+/// });
+/// ```
+pub struct TypedSocket<In, Out, St = SocketStream> {
+    stream: St,
+    format: Format,
+    // a close frame waiting to be sent.
+    outbox: Outbox,
+    closing: bool,
+    _in: PhantomData<fn() -> In>,
+    _out: PhantomData<fn(Out)>,
+}
+
+impl<In, Out, St> TypedSocket<In, Out, St> {
+    fn new(stream: St, format: Format) -> Self {
+        Self {
+            stream,
+            format,
+            outbox: Outbox::default(),
+            closing: false,
+            _in: PhantomData,
+            _out: PhantomData,
+        }
+    }
+
+    /// Construct a typed socket speaking JSON over text frames.
+    #[cfg(feature = "json")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "json")))]
+    pub fn json(stream: St) -> Self {
+        Self::new(stream, Format::Json)
+    }
+
+    /// Construct a typed socket speaking MessagePack over binary frames.
+    #[cfg(feature = "msgpack")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "msgpack")))]
+    pub fn msgpack(stream: St) -> Self {
+        Self::new(stream, Format::MessagePack)
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut St {
+        &mut self.stream
+    }
+
+    /// Consume the typed socket, return the underlying stream.
+    pub fn into_inner(self) -> St {
+        self.stream
+    }
+}
+
+impl<In, Out, St> TypedSocket<In, Out, St>
+where
+    St: Unpin + Sink<Message, Error = WsError>,
+{
+    /// Send a close frame for a protocol error.
+    fn start_close(&mut self, err: &TypedError) {
+        if self.closing {
+            return;
+        }
+        let (code, reason) = match err {
+            TypedError::Unsupported => (CloseCode::Unsupported, UNSUPPORTED),
+            TypedError::Invalid(_) => (CloseCode::Invalid, INVALID),
+            _ => return,
+        };
+        self.closing = true;
+        self.outbox.put(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        })));
+    }
+
+    /// Send the pending close frame and flush.
+    fn poll_pending(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), TypedError>> {
+        self.outbox.poll(&mut self.stream, cx).map_err(Into::into)
+    }
+}
+
+impl<In, Out, St> Stream for TypedSocket<In, Out, St>
+where
+    In: DeserializeOwned,
+    St: Unpin + Stream<Item = Result<Message, WsError>> + Sink<Message, Error = WsError>,
+{
+    type Item = Result<In, TypedError>;
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Poll::Ready(Err(err)) = self.poll_pending(cx) {
+                return Poll::Ready(Some(Err(err)));
+            }
+            let message = match futures::ready!(Pin::new(&mut self.stream).poll_next(cx))
+            {
+                None
+                | Some(Err(WsError::ConnectionClosed))
+                | Some(Err(WsError::AlreadyClosed)) => return Poll::Ready(None),
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                Some(Ok(message)) => message,
+            };
+            match message {
+                // replied by tungstenite.
+                Message::Ping(_) | Message::Pong(_) | Message::Close(_) => (),
+                // drop messages received during the close handshake.
+                _ if self.closing => (),
+                message => {
+                    let result = self.format.decode(message);
+                    if let Err(ref err) = result {
+                        self.start_close(err);
+                    }
+                    return Poll::Ready(Some(result));
+                }
+            }
+        }
+    }
+}
+
+impl<In, Out, St> Sink<Out> for TypedSocket<In, Out, St>
+where
+    Out: Serialize,
+    St: Unpin + Sink<Message, Error = WsError>,
+{
+    type Error = TypedError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_pending(cx))?;
+        Pin::new(&mut self.stream)
+            .poll_ready(cx)
+            .map_err(Into::into)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Out) -> Result<(), Self::Error> {
+        let message = self.format.encode(&item)?;
+        Pin::new(&mut self.stream).start_send(message)?;
+        Ok(())
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_pending(cx))?;
+        Pin::new(&mut self.stream)
+            .poll_flush(cx)
+            .map_err(Into::into)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.poll_pending(cx))?;
+        Pin::new(&mut self.stream)
+            .poll_close(cx)
+            .map_err(Into::into)
+    }
+}
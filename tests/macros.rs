@@ -0,0 +1,108 @@
+use async_std::task::spawn;
+use roa::extract::{Json, Path, Query};
+use roa::http::StatusCode;
+use roa::preload::*;
+use roa::router::{get, post, put, routes};
+use roa::{App, Context};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+struct Id {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct Filter {
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct User {
+    name: String,
+}
+
+#[get("/")]
+async fn index(ctx: &mut Context) -> roa::Result {
+    ctx.resp.write("index");
+    Ok(())
+}
+
+#[get("/user/:id")]
+async fn get_user(Path(id): Path<Id>, Query(filter): Query<Filter>) -> String {
+    format!("{}:{}", id.id, filter.name)
+}
+
+#[put("/user/:id")]
+async fn update_user(Path(id): Path<Id>, Json(user): Json<User>) -> String {
+    format!("{}:{}", id.id, user.name)
+}
+
+#[post("/user")]
+async fn create_user(Json(user): Json<User>) -> (StatusCode, String) {
+    (StatusCode::CREATED, user.name)
+}
+
+#[tokio::test]
+async fn routes() -> Result<(), Box<dyn std::error::Error>> {
+    let router = routes![index, get_user, update_user, create_user];
+    let app = App::new().end(router.routes("/api")?);
+    let (addr, server) = app.run()?;
+    spawn(server);
+    let client = reqwest::Client::new();
+
+    let resp = client.get(&format!("http://{}/api", addr)).send().await?;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!("index", resp.text().await?);
+
+    let resp = client
+        .get(&format!("http://{}/api/user/1?name=Hexilee", addr))
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!("1:Hexilee", resp.text().await?);
+
+    let resp = client
+        .put(&format!("http://{}/api/user/1", addr))
+        .json(&User {
+            name: "Alice".to_string(),
+        })
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!("1:Alice", resp.text().await?);
+
+    let resp = client
+        .post(&format!("http://{}/api/user", addr))
+        .json(&User {
+            name: "Bob".to_string(),
+        })
+        .send()
+        .await?;
+    assert_eq!(StatusCode::CREATED, resp.status());
+    assert_eq!("Bob", resp.text().await?);
+
+    let resp = client
+        .delete(&format!("http://{}/api/user/1", addr))
+        .send()
+        .await?;
+    assert_eq!(StatusCode::METHOD_NOT_ALLOWED, resp.status());
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_handlers() {
+    let id = Id { id: 1 };
+    let filter = Filter {
+        name: "Hexilee".to_string(),
+    };
+    assert_eq!("1:Hexilee", get_user(Path(id), Query(filter)).await);
+    let user = User {
+        name: "Bob".to_string(),
+    };
+    assert_eq!(
+        (StatusCode::CREATED, "Bob".to_string()),
+        create_user(Json(user)).await
+    );
+    assert_eq!("/user/:id", get_user::PATH);
+    assert_eq!(roa::http::Method::POST, create_user::METHOD);
+}
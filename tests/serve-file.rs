@@ -1,6 +1,6 @@
 use async_std::fs::read_to_string;
 use async_std::task::spawn;
-use http::header::ACCEPT_ENCODING;
+use http::header::{ACCEPT_ENCODING, CONTENT_LENGTH};
 use roa::body::DispositionType;
 use roa::compress::Compress;
 use roa::preload::*;
@@ -21,6 +21,26 @@ async fn serve_static_file() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn serve_file_length() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test(ctx: &mut Context) -> roa::Result {
+        ctx.write_file("assets/author.txt", DispositionType::Inline)
+            .await
+    }
+    let app = App::new().end(test);
+    let (addr, server) = app.run()?;
+    spawn(server);
+    let client = reqwest::Client::new();
+    let resp = client.get(&format!("http://{}", addr)).send().await?;
+    assert_eq!("7", resp.headers()[CONTENT_LENGTH]);
+    assert_eq!("Hexilee", resp.text().await?);
+
+    let resp = client.head(&format!("http://{}", addr)).send().await?;
+    assert_eq!("7", resp.headers()[CONTENT_LENGTH]);
+    assert_eq!("", resp.text().await?);
+    Ok(())
+}
+
 #[tokio::test]
 async fn serve_router_variable() -> Result<(), Box<dyn std::error::Error>> {
     async fn test(ctx: &mut Context) -> roa::Result {
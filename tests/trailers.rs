@@ -0,0 +1,57 @@
+use async_std::net::TcpStream;
+use async_std::task::spawn;
+use futures::{AsyncReadExt, AsyncWriteExt};
+use hyper::body::HttpBody;
+use hyper::Client;
+use roa::preload::*;
+use roa::{App, Context};
+
+async fn checksum(ctx: &mut Context) -> roa::Result {
+    let trailers = ctx.resp.trailers();
+    let mut sender = ctx.resp.channel(1);
+    ctx.exec.spawn(async move {
+        let mut length = 0;
+        for data in &["Hello", ", ", "World"] {
+            length += data.len();
+            sender.send(*data).await?;
+        }
+        trailers.insert("x-length", length.into());
+        Ok::<_, std::io::Error>(())
+    });
+    Ok(())
+}
+
+#[tokio::test]
+async fn trailers_over_http2() -> Result<(), Box<dyn std::error::Error>> {
+    let (addr, server) = App::new().end(checksum).run()?;
+    spawn(server);
+    let client = Client::builder()
+        .http2_only(true)
+        .build_http::<hyper::Body>();
+    let resp = client.get(format!("http://{}", addr).parse()?).await?;
+    let mut body = resp.into_body();
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        data.extend_from_slice(&chunk?);
+    }
+    assert_eq!(b"Hello, World", &*data);
+    let trailers = body.trailers().await?.expect("trailers should be sent");
+    assert_eq!("12", trailers["x-length"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn trailers_dropped_over_http1() -> Result<(), Box<dyn std::error::Error>> {
+    let (addr, server) = App::new().end(checksum).run()?;
+    spawn(server);
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nTE: trailers\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await?;
+    assert!(resp.contains("transfer-encoding: chunked\r\n"));
+    assert!(resp.ends_with("\r\n0\r\n\r\n"));
+    assert!(!resp.to_lowercase().contains("x-length"));
+    Ok(())
+}